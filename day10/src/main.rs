@@ -1,113 +1,474 @@
 #![cfg_attr(test, feature(test))]
 
-type In = Instr;
-type Out = i32;
+use std::fmt::{self, Display};
+use std::mem;
+use std::str::FromStr;
 
-#[derive(Debug, Copy, Clone)]
+use util::sim::{hash_of, Simulation};
+
+type In = Vec<Instr>;
+type Out = String;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 enum Instr {
     Noop,
     AddX(i32),
 }
 
-fn parse(s: &'static str) -> In {
-    if s == "noop" {
-        Instr::Noop
-    } else {
-        Instr::AddX(s[5..].parse().unwrap())
+// Every instruction, by mnemonic, with how many cycles it takes to complete.
+// Operands in the patterns here are placeholders.
+const OPCODES: &[(Instr, &str, usize)] = &[(Instr::Noop, "noop", 1), (Instr::AddX(0), "addx", 2)];
+
+impl Instr {
+    fn opcode(self) -> &'static (Instr, &'static str, usize) {
+        let kind = mem::discriminant(&self);
+        let entry = OPCODES
+            .iter()
+            .find(|(op, _, _)| mem::discriminant(op) == kind);
+        entry.expect("every instruction has an opcode")
     }
-}
 
-fn part1(n: &[In]) -> Out {
-    let mut x = 1;
-    let mut cc = 0;
-    let mut ip = 0;
-    let mut foo = false;
+    fn mnemonic(self) -> &'static str {
+        self.opcode().1
+    }
 
-    let mut total = 0;
+    fn cycles(self) -> usize {
+        self.opcode().2
+    }
 
-    loop {
-        cc += 1;
+    // Takes effect at the end of the instruction's last cycle.
+    fn execute(self, x: &mut i32) {
+        match self {
+            Instr::Noop => (),
+            Instr::AddX(v) => *x += v,
+        }
+    }
+}
 
-        let mut dx = 0;
+impl Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        match self {
+            Instr::Noop => Ok(()),
+            Instr::AddX(v) => write!(f, " {v}"),
+        }
+    }
+}
 
-        match n[ip] {
-            Instr::Noop => {
-                ip += 1;
-            }
-            Instr::AddX(v) => {
-                if foo {
-                    foo = false;
-                    dx = v;
-                    ip += 1;
-                } else {
-                    foo = true;
-                }
+impl FromStr for Instr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let m = words.next().ok_or("empty instruction")?;
+        let (op, _, _) = OPCODES
+            .iter()
+            .find(|(_, mnemonic, _)| *mnemonic == m)
+            .ok_or_else(|| format!("unknown instruction {m:?}"))?;
+        let instr = match op {
+            Instr::Noop => Instr::Noop,
+            Instr::AddX(_) => {
+                let arg = words.next().ok_or(format!("{m} needs an operand"))?;
+                Instr::AddX(
+                    arg.parse()
+                        .map_err(|e| format!("bad operand {arg:?}: {e}"))?,
+                )
             }
+        };
+        match words.next() {
+            Some(extra) => Err(format!("unexpected {extra:?} after {}", instr.mnemonic())),
+            None => Ok(instr),
         }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AsmError {
+    line: usize,
+    message: String,
+}
 
-        if (cc - 20) % 40 == 0 {
-            let strength = cc * x;
-            total += strength;
+impl Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+// Programs as text, one instruction per line; blank lines and `;` comments are skipped.
+fn assemble(src: &str) -> Result<Vec<Instr>, AsmError> {
+    let mut program = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        let code = line.split(';').next().unwrap().trim();
+        if code.is_empty() {
+            continue;
         }
+        let instr = code.parse().map_err(|message| AsmError {
+            line: i + 1,
+            message,
+        })?;
+        program.push(instr);
+    }
+    Ok(program)
+}
+
+fn parse(s: &'static str) -> In {
+    assemble(s).unwrap_or_else(|e| panic!("{e}"))
+}
+
+#[allow(dead_code)]
+fn disassemble(program: &[Instr]) -> String {
+    let lines = program.iter().map(|instr| format!("{instr}\n"));
+    String::from_iter(lines)
+}
 
-        x += dx;
+// The machine as it is *during* a cycle, before that cycle's instruction has finished.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct Cycle {
+    number: usize,
+    x: i32,
+    ip: usize,
+    instr: Instr,
+    // which of the instruction's cycles this is, from 1
+    step: usize,
+}
+
+impl Cycle {
+    fn signal_strength(&self) -> i32 {
+        self.number as i32 * self.x
+    }
+}
+
+impl Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (number, x, ip) = (self.number, self.x, self.ip);
+        let (instr, step, cycles) = (self.instr, self.step, self.instr.cycles());
+        write!(f, "cycle={number} x={x} ip={ip} {instr} ({step}/{cycles})")
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+enum Breakpoint {
+    Cycle(usize),
+    // the first cycle of the instruction at this address
+    Ip(usize),
+}
 
-        if cc == 220 {
-            break;
+#[derive(Clone)]
+struct Cpu<'a> {
+    program: &'a [Instr],
+    x: i32,
+    cycle: usize,
+    ip: usize,
+    elapsed: usize,
+    breakpoints: Vec<Breakpoint>,
+    trace: bool,
+}
+
+impl<'a> Cpu<'a> {
+    fn new(program: &'a [Instr]) -> Self {
+        Self {
+            program,
+            x: 1,
+            cycle: 0,
+            ip: 0,
+            elapsed: 0,
+            breakpoints: Vec::new(),
+            trace: false,
         }
     }
-    total
+
+    fn is_halted(&self) -> bool {
+        self.ip >= self.program.len()
+    }
+
+    // The cycle that `next` will run, if the program hasn't finished.
+    fn upcoming(&self) -> Option<Cycle> {
+        let instr = *self.program.get(self.ip)?;
+        Some(Cycle {
+            number: self.cycle + 1,
+            x: self.x,
+            ip: self.ip,
+            instr,
+            step: self.elapsed + 1,
+        })
+    }
 }
 
-fn part2(n: &[In]) -> Out {
-    let mut x = 1;
-    let mut cc = 0;
-    let mut ip = 0;
-    let mut foo = false;
+// Debugging aids; neither part needs them.
+#[allow(dead_code)]
+impl Cpu<'_> {
+    fn breakpoint(mut self, bp: Breakpoint) -> Self {
+        self.breakpoints.push(bp);
+        self
+    }
 
-    let mut grid = vec![];
+    // Prints every cycle to stderr.
+    fn trace(mut self, on: bool) -> Self {
+        self.trace = on;
+        self
+    }
 
-    loop {
-        cc += 1;
+    // Runs up to and including the next cycle that hits a breakpoint.
+    fn run_to_break(&mut self) -> Option<Cycle> {
+        let breakpoints = self.breakpoints.clone();
+        self.find(|c| {
+            breakpoints.iter().any(|bp| match *bp {
+                Breakpoint::Cycle(n) => c.number == n,
+                Breakpoint::Ip(ip) => c.ip == ip && c.step == 1,
+            })
+        })
+    }
+}
 
-        let mut dx = 0;
+impl Iterator for Cpu<'_> {
+    type Item = Cycle;
 
-        match n[ip] {
-            Instr::Noop => {
-                ip += 1;
-            }
-            Instr::AddX(v) => {
-                if foo {
-                    foo = false;
-                    dx = v;
-                    ip += 1;
-                } else {
-                    foo = true;
-                }
-            }
+    fn next(&mut self) -> Option<Cycle> {
+        let during = self.upcoming()?;
+        let instr = during.instr;
+        self.cycle += 1;
+
+        if self.trace {
+            eprintln!("{during}");
+        }
+
+        self.elapsed += 1;
+        if self.elapsed == instr.cycles() {
+            instr.execute(&mut self.x);
+            self.ip += 1;
+            self.elapsed = 0;
         }
 
-        let raster = (cc - 1) % 40;
+        Some(during)
+    }
+}
+
+// The CPU wired up to the signal sampler and the CRT.
+struct Device<'a> {
+    cpu: Cpu<'a>,
+    limit: usize,
+    total: i32,
+    grid: Vec<[bool; 40]>,
+}
+
+impl<'a> Device<'a> {
+    fn new(program: &'a [Instr], limit: usize) -> Self {
+        Self {
+            cpu: Cpu::new(program),
+            limit,
+            total: 0,
+            grid: vec![],
+        }
+    }
+}
+
+impl Simulation for Device<'_> {
+    fn step(&mut self) {
+        let Some(cycle) = self.cpu.next() else { return };
+
+        if cycle.number % 40 == 20 {
+            self.total += cycle.signal_strength();
+        }
+
+        let raster = (cycle.number - 1) % 40;
         if raster == 0 {
-            grid.push([' '; 40]);
+            self.grid.push([false; 40]);
         }
-        let pixel = &mut grid.last_mut().unwrap()[raster];
-        if (x - 1..=x + 1).contains(&(raster as _)) {
-            *pixel = '█';
+        let pixel = &mut self.grid.last_mut().unwrap()[raster];
+        if (cycle.x - 1..=cycle.x + 1).contains(&(raster as _)) {
+            *pixel = true;
         }
+    }
 
-        x += dx;
+    fn is_done(&self) -> bool {
+        self.cpu.cycle == self.limit || self.cpu.is_halted()
+    }
 
-        if cc == 240 {
-            break;
+    fn render(&self) -> String {
+        let mut s = String::new();
+        for row in &self.grid {
+            s.extend(row.map(|b| if b { '█' } else { ' ' }));
+            s.push('\n');
+        }
+        // the cycle about to run, numbered like the ones the CPU hands out
+        let cpu = &self.cpu;
+        match cpu.upcoming() {
+            Some(cycle) => s += &format!("next: {cycle}"),
+            None => s += &format!("halted after cycle={} x={}", cpu.cycle, cpu.x),
         }
+        s
     }
 
-    for row in grid {
-        println!("{}", String::from_iter(row));
+    fn fingerprint(&self) -> u64 {
+        let cpu = &self.cpu;
+        hash_of(&(cpu.x, cpu.ip, cpu.elapsed))
     }
+}
 
-    0
+fn part1(n: &In) -> Out {
+    let mut device = Device::new(n, 220);
+    util::sim::run(&mut device);
+    device.total.to_string()
 }
 
-util::register!(parse, part1, part2);
+fn part2(n: &In) -> Out {
+    let mut device = Device::new(n, 240);
+    util::sim::run(&mut device);
+    let grid = device.grid;
+
+    // An answer with a '?' in it is no answer; show the screen and name each glyph that failed.
+    util::ocr::recognize(&grid).unwrap_or_else(|e| {
+        for row in &grid {
+            let row = row.map(|b| if b { '█' } else { ' ' });
+            eprintln!("{}", String::from_iter(row));
+        }
+        panic!("{e}")
+    })
+}
+
+util::register!(parse, part1, part2, @alt);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A program that lights exactly the `#`s of `picture`, rows of 40 pixels. Each `addx` holds
+    // X still for two pixels, so it picks an X that covers the pair just as the picture needs.
+    // X starts at 1, lighting both of the first two pixels.
+    fn painting(picture: &[&str]) -> Vec<Instr> {
+        let pixels: Vec<bool> = picture
+            .iter()
+            .flat_map(|row| format!("{row:.<40}").into_bytes())
+            .map(|b| b == b'#')
+            .collect();
+        assert!(pixels[0] && pixels[1]);
+
+        let mut x = 1;
+        let mut src = String::new();
+        for (i, pair) in pixels.chunks(2).enumerate().skip(1) {
+            let r = (i * 2 % 40) as i32;
+            let next = match (pair[0], pair[1]) {
+                (true, true) => r,
+                (true, false) => r - 1,
+                (false, true) => r + 2,
+                (false, false) => -10,
+            };
+            src += &format!("addx {}\n", next - x);
+            x = next;
+        }
+        src += "addx 0 ; hold the last pair\n";
+        assemble(&src).unwrap()
+    }
+
+    const EZ: [&str; 6] = [
+        "####.####",
+        "#.......#",
+        "###....#.",
+        "#.....#..",
+        "#....#...",
+        "####.####",
+    ];
+
+    #[test]
+    fn reads_the_screen() {
+        assert_eq!(part2(&painting(&EZ)), "EZ");
+    }
+
+    #[test]
+    #[should_panic(expected = "glyph 2 at column 10")]
+    fn names_unreadable_glyphs() {
+        let smudged = EZ.map(|row| format!("{row}.#"));
+        let smudged: Vec<&str> = smudged.iter().map(String::as_str).collect();
+        part2(&painting(&smudged));
+    }
+
+    const SHORT: &str = "
+        noop
+        addx 3  ; x = 4 from cycle 4
+        addx -5
+
+        noop
+    ";
+
+    #[test]
+    fn assembles_and_disassembles() {
+        let program = assemble(SHORT).unwrap();
+        assert_eq!(
+            program,
+            [Instr::Noop, Instr::AddX(3), Instr::AddX(-5), Instr::Noop]
+        );
+        let text = disassemble(&program);
+        assert_eq!(text, "noop\naddx 3\naddx -5\nnoop\n");
+        assert_eq!(assemble(&text), Ok(program));
+    }
+
+    #[test]
+    fn times_instructions_from_the_table() {
+        assert_eq!(Instr::Noop.cycles(), 1);
+        assert_eq!(Instr::AddX(-7).cycles(), 2);
+        for &(op, mnemonic, cycles) in OPCODES {
+            let text = op.to_string();
+            assert!(text.starts_with(mnemonic));
+            let parsed: Instr = text.parse().unwrap();
+            assert_eq!((parsed.mnemonic(), parsed.cycles()), (mnemonic, cycles));
+        }
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let err = |src| assemble(src).unwrap_err().to_string();
+        assert_eq!(
+            err("noop\n\njump 3"),
+            "line 3: unknown instruction \"jump\""
+        );
+        assert_eq!(err("addx ; none"), "line 1: addx needs an operand");
+        assert_eq!(err("noop 1"), "line 1: unexpected \"1\" after noop");
+        assert!(err("noop\naddx x").starts_with("line 2: bad operand \"x\""));
+    }
+
+    #[test]
+    fn stops_at_breakpoints() {
+        let program = assemble(SHORT).unwrap();
+        let mut cpu = Cpu::new(&program)
+            .trace(true)
+            .breakpoint(Breakpoint::Cycle(3))
+            .breakpoint(Breakpoint::Ip(2))
+            .breakpoint(Breakpoint::Cycle(6));
+
+        let hit = cpu.run_to_break().unwrap();
+        assert_eq!((hit.number, hit.x, hit.ip, hit.step), (3, 1, 1, 2));
+        // addx -5 starts on cycle 4, once addx 3 has landed
+        let hit = cpu.run_to_break().unwrap();
+        assert_eq!((hit.number, hit.x, hit.ip, hit.step), (4, 4, 2, 1));
+        assert_eq!(hit.to_string(), "cycle=4 x=4 ip=2 addx -5 (1/2)");
+        let hit = cpu.run_to_break().unwrap();
+        assert_eq!((hit.number, hit.x, hit.instr), (6, -1, Instr::Noop));
+        assert_eq!(cpu.run_to_break(), None);
+        assert!(cpu.is_halted());
+    }
+
+    #[test]
+    fn unreached_breakpoints_never_hit() {
+        let program = assemble("addx 1\nnoop").unwrap();
+        let mut cpu = Cpu::new(&program).breakpoint(Breakpoint::Ip(7));
+        assert_eq!(cpu.run_to_break(), None);
+        assert_eq!(cpu.cycle, 3);
+    }
+
+    #[test]
+    fn renders_the_upcoming_cycle() {
+        let program = assemble(SHORT).unwrap();
+        let mut device = Device::new(&program, 240);
+        assert!(device
+            .render()
+            .ends_with("next: cycle=1 x=1 ip=0 noop (1/1)"));
+        device.step();
+        device.step();
+        assert!(device
+            .render()
+            .ends_with("next: cycle=3 x=1 ip=1 addx 3 (2/2)"));
+        util::sim::run(&mut device);
+        assert!(device.render().ends_with("halted after cycle=6 x=-1"));
+    }
+}
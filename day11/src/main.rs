@@ -1,5 +1,11 @@
 #![cfg_attr(test, feature(test))]
 
+use std::collections::HashMap;
+use std::iter::Peekable;
+
+use num_bigint::BigUint;
+use num_traits::Zero;
+
 type In = Vec<Monkey>;
 type Out = u64;
 
@@ -10,22 +16,118 @@ struct Monkey {
     test: u64,
     if_true: usize,
     if_false: usize,
-    inspections: u64,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone, Debug, PartialEq)]
 enum Op {
-    Add(u64),
-    Mul(u64),
-    Square,
+    Old,
+    Const(u64),
+    Add(Box<Op>, Box<Op>),
+    Mul(Box<Op>, Box<Op>),
+}
+
+// Anything a worry level can be: plain numbers reduced by a modulus, or exact big integers.
+trait Worry: Clone {
+    // `n` as the same kind of worry as `self`, e.g. under the same modulus.
+    fn constant(&self, n: u64) -> Self;
+    fn add(&self, other: &Self) -> Self;
+    fn mul(&self, other: &Self) -> Self;
+}
+
+// A worry level modulo `modulus`, reduced after every operation so none can overflow.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct Residue {
+    n: u64,
+    modulus: u64,
+}
+
+impl Residue {
+    fn new(n: u64, modulus: u64) -> Self {
+        Self {
+            n: n % modulus,
+            modulus,
+        }
+    }
+
+    fn reduce(&self, n: u128) -> Self {
+        Self {
+            n: (n % self.modulus as u128) as u64,
+            modulus: self.modulus,
+        }
+    }
+}
+
+impl Worry for Residue {
+    fn constant(&self, n: u64) -> Self {
+        Self::new(n, self.modulus)
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        self.reduce(self.n as u128 + other.n as u128)
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        self.reduce(self.n as u128 * other.n as u128)
+    }
+}
+
+impl Worry for BigUint {
+    fn constant(&self, n: u64) -> Self {
+        n.into()
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        self * other
+    }
 }
 
 impl Op {
-    pub fn apply(&self, n: u64) -> u64 {
+    pub fn apply<W: Worry>(&self, old: &W) -> W {
         match self {
-            &Op::Add(a) => n + a,
-            &Op::Mul(a) => n * &a,
-            Op::Square => n * n,
+            Op::Old => old.clone(),
+            Op::Const(c) => old.constant(*c),
+            Op::Add(a, b) => a.apply(old).add(&b.apply(old)),
+            Op::Mul(a, b) => a.apply(old).mul(&b.apply(old)),
+        }
+    }
+
+    fn parse(s: &str) -> Option<Op> {
+        let spaced = ["+", "*", "(", ")"]
+            .iter()
+            .fold(s.to_owned(), |s, t| s.replace(t, &format!(" {t} ")));
+        let mut toks = spaced.split_whitespace().peekable();
+        let op = Self::parse_sum(&mut toks)?;
+        toks.next().is_none().then_some(op)
+    }
+
+    fn parse_sum<'a>(toks: &mut Peekable<impl Iterator<Item = &'a str>>) -> Option<Op> {
+        let mut op = Self::parse_product(toks)?;
+        while toks.next_if_eq(&"+").is_some() {
+            op = Op::Add(Box::new(op), Box::new(Self::parse_product(toks)?));
+        }
+        Some(op)
+    }
+
+    fn parse_product<'a>(toks: &mut Peekable<impl Iterator<Item = &'a str>>) -> Option<Op> {
+        let mut op = Self::parse_term(toks)?;
+        while toks.next_if_eq(&"*").is_some() {
+            op = Op::Mul(Box::new(op), Box::new(Self::parse_term(toks)?));
+        }
+        Some(op)
+    }
+
+    fn parse_term<'a>(toks: &mut Peekable<impl Iterator<Item = &'a str>>) -> Option<Op> {
+        match toks.next()? {
+            "old" => Some(Op::Old),
+            "(" => {
+                let op = Self::parse_sum(toks)?;
+                toks.next_if_eq(&")").map(|_| op)
+            }
+            n => n.parse().ok().map(Op::Const),
         }
     }
 }
@@ -39,14 +141,7 @@ fn parse_monkey(mut lines: impl Iterator<Item = &'static str>) -> Option<Monkey>
         .collect::<Vec<u64>>();
 
     let op_line = lines.next()?;
-    let s = op_line.strip_prefix("Operation: new = old ")?;
-    let op = if s == "* old" {
-        Op::Square
-    } else if s.starts_with("*") {
-        Op::Mul(s[2..].parse().ok()?)
-    } else {
-        Op::Add(s[2..].parse().ok()?)
-    };
+    let op = Op::parse(op_line.strip_prefix("Operation: new = ")?)?;
 
     let test = lines.next()?.split_whitespace().last()?.parse().ok()?;
     let if_true = lines.next()?.split_whitespace().last()?.parse().ok()?;
@@ -60,7 +155,6 @@ fn parse_monkey(mut lines: impl Iterator<Item = &'static str>) -> Option<Monkey>
         test,
         if_true,
         if_false,
-        inspections: 0,
     })
 }
 
@@ -75,46 +169,236 @@ fn parse(s: &'static str) -> In {
     ms
 }
 
-fn round(monkeys: &mut [Monkey], reducer: impl Fn(u64) -> u64) {
-    for i in 0..monkeys.len() {
-        for mut item in std::mem::take(&mut monkeys[i].items) {
-            monkeys[i].inspections += 1;
-            let monkey = &monkeys[i];
-            item = reducer(monkey.op.apply(item));
-            let monkey_index = if item % monkey.test == 0 {
-                monkey.if_true
-            } else {
-                monkey.if_false
-            };
-            monkeys[monkey_index].items.push(item);
-        }
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
     }
 }
 
-fn part1(n: &In) -> Out {
-    let mut monkeys = n.clone();
+fn monkey_business(inspections: &[u64]) -> u64 {
+    let mut inspections = inspections.to_vec();
+    inspections.sort();
+    inspections.iter().rev().take(2).product()
+}
 
-    for _ in 0..20 {
-        round(&mut monkeys, |n| n / 3);
+// Plays every item through `rounds` rounds with no reduction beyond `relief`,
+// so nothing can overflow; only practical while the numbers stay small.
+fn exact_inspections(monkeys: &[Monkey], rounds: usize, relief: u64) -> Vec<u64> {
+    let mut items = Vec::from_iter(
+        monkeys
+            .iter()
+            .map(|m| Vec::from_iter(m.items.iter().map(|&i| BigUint::from(i)))),
+    );
+    let mut inspections = vec![0; monkeys.len()];
+
+    for _ in 0..rounds {
+        for (i, monkey) in monkeys.iter().enumerate() {
+            for item in std::mem::take(&mut items[i]) {
+                inspections[i] += 1;
+                let item = monkey.op.apply(&item) / relief;
+                let target = if (&item % monkey.test).is_zero() {
+                    monkey.if_true
+                } else {
+                    monkey.if_false
+                };
+                items[target].push(item);
+            }
+        }
     }
 
-    let mut inspections = monkeys.iter().map(|m| m.inspections).collect::<Vec<_>>();
-    inspections.sort();
-    inspections.iter().rev().take(2).product()
+    inspections
 }
 
-fn part2(n: &In) -> Out {
-    let mut monkeys = n.clone();
+// Items never affect each other once worry is only reduced modulo the lcm of the tests,
+// so each one is followed on its own until it comes back to a state it's been in before,
+// and the rest of the rounds are skipped over a whole cycle at a time.
+fn modular_inspections(monkeys: &[Monkey], rounds: usize) -> Vec<u64> {
+    let lcm = monkeys
+        .iter()
+        .try_fold(1u64, |acc, m| (acc / gcd(acc, m.test)).checked_mul(m.test))
+        .expect("lcm of the tests doesn't fit in 64 bits");
 
-    let modulus = monkeys.iter().map(|m| m.test).product::<u64>();
+    let mut inspections = vec![0; monkeys.len()];
 
-    for _ in 0..10000 {
-        round(&mut monkeys, |n| n % modulus);
+    for (start, monkey) in monkeys.iter().enumerate() {
+        for &item in &monkey.items {
+            let mut state = (start, Residue::new(item, lcm));
+            // where in `history` each state was first seen
+            let mut seen = HashMap::new();
+            // the round and inspections so far when each state was seen
+            let mut history: Vec<(usize, Vec<u64>)> = Vec::new();
+            let mut counts = vec![0; monkeys.len()];
+
+            let mut round = 0;
+            while round < rounds {
+                if let Some(&first) = seen.get(&state) {
+                    let (then, ref before) = history[first];
+                    let period = round - then;
+                    let cycles = ((rounds - round) / period) as u64;
+                    for (c, h) in counts.iter_mut().zip(before) {
+                        *c += (*c - h) * cycles;
+                    }
+                    round += period * cycles as usize;
+                    // fewer than `period` rounds are left, so no state comes round again
+                    seen.clear();
+                    history.clear();
+                    if round == rounds {
+                        break;
+                    }
+                } else {
+                    seen.insert(state, history.len());
+                    history.push((round, counts.clone()));
+                }
+
+                // within a round, an item keeps going as long as it's thrown to later monkeys
+                let (mut at, mut worry) = state;
+                loop {
+                    let m = &monkeys[at];
+                    counts[at] += 1;
+                    worry = m.op.apply(&worry);
+                    let target = if worry.n % m.test == 0 {
+                        m.if_true
+                    } else {
+                        m.if_false
+                    };
+                    let next_round = target <= at;
+                    at = target;
+                    if next_round {
+                        break;
+                    }
+                }
+                state = (at, worry);
+                round += 1;
+            }
+
+            for (total, c) in inspections.iter_mut().zip(counts) {
+                *total += c;
+            }
+        }
     }
 
-    let mut inspections = monkeys.iter().map(|m| m.inspections).collect::<Vec<_>>();
-    inspections.sort();
-    inspections.iter().rev().take(2).product()
+    inspections
+}
+
+fn part1(n: &In) -> Out {
+    monkey_business(&exact_inspections(n, 20, 3))
+}
+
+fn part2(n: &In) -> Out {
+    // `--opt worry=exact` checks the answer without reducing worry at all, slowly
+    let inspections = match util::opt("worry").as_deref() {
+        Some("exact") => exact_inspections(n, 10000, 1),
+        None | Some("modular") => modular_inspections(n, 10000),
+        Some(w) => panic!("unknown worry mode {w:?}"),
+    };
+    monkey_business(&inspections)
 }
 
 util::register!(parse, part1, part2, @alt);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every round in full, reducing modulo the lcm like `modular_inspections` but never
+    // skipping any.
+    fn straight_inspections(monkeys: &[Monkey], rounds: usize) -> Vec<u64> {
+        let lcm = monkeys
+            .iter()
+            .fold(1, |acc, m| acc / gcd(acc, m.test) * m.test);
+        let mut items = Vec::from_iter(
+            monkeys
+                .iter()
+                .map(|m| Vec::from_iter(m.items.iter().map(|&i| Residue::new(i, lcm)))),
+        );
+        let mut inspections = vec![0; monkeys.len()];
+        for _ in 0..rounds {
+            for (i, monkey) in monkeys.iter().enumerate() {
+                for item in std::mem::take(&mut items[i]) {
+                    inspections[i] += 1;
+                    let item = monkey.op.apply(&item);
+                    let target = if item.n % monkey.test == 0 {
+                        monkey.if_true
+                    } else {
+                        monkey.if_false
+                    };
+                    items[target].push(item);
+                }
+            }
+        }
+        inspections
+    }
+
+    // Worry stays small enough to follow exactly for a few hundred rounds, and with only
+    // 3 * 6 states for an item to be in, each one repeats within the first 18 rounds.
+    const SMALL: &str = "\
+Monkey 0:
+  Starting items: 1, 4
+  Operation: new = old * 2 + 1
+  Test: divisible by 2
+    If true: throw to monkey 1
+    If false: throw to monkey 2
+
+Monkey 1:
+  Starting items: 3
+  Operation: new = old + 5
+  Test: divisible by 3
+    If true: throw to monkey 0
+    If false: throw to monkey 2
+
+Monkey 2:
+  Starting items: 7, 2
+  Operation: new = (old + 1) * 2
+  Test: divisible by 3
+    If true: throw to monkey 0
+    If false: throw to monkey 1
+";
+
+    #[test]
+    fn skipping_cycles_changes_nothing() {
+        let monkeys = parse(SMALL);
+        for rounds in [1, 17, 18, 19, 100, 257] {
+            let skipped = modular_inspections(&monkeys, rounds);
+            assert_eq!(skipped, straight_inspections(&monkeys, rounds), "{rounds}");
+            assert_eq!(skipped, exact_inspections(&monkeys, rounds, 1), "{rounds}");
+        }
+
+        let example = parse(TEST_INPUT);
+        let skipped = modular_inspections(&example, 10000);
+        assert_eq!(skipped, [52166, 47830, 1938, 52013]);
+        assert_eq!(skipped, straight_inspections(&example, 10000));
+        // the example squares its worry too often to follow exactly for long
+        assert_eq!(
+            modular_inspections(&example, 12),
+            exact_inspections(&example, 12, 1)
+        );
+    }
+
+    #[test]
+    fn skips_far_past_what_it_could_simulate() {
+        let monkeys = parse(SMALL);
+        // a multiple of every period up to 18, so each stretch of `r` rounds after the first
+        // inspects exactly the same
+        let r = 12252240 * 100_000;
+        let [a, b, c] = [1, 2, 3].map(|k| modular_inspections(&monkeys, k * r));
+        for m in 0..monkeys.len() {
+            assert_eq!(b[m] - a[m], c[m] - b[m]);
+        }
+        // every item is thrown at least once a round
+        assert!(a.iter().sum::<u64>() >= 5 * r as u64);
+    }
+
+    #[test]
+    fn residues_never_overflow() {
+        let cube = Op::parse("old * old * old + 7").unwrap();
+        let modulus = u64::MAX - 58;
+        let old = u64::MAX - 100;
+        let exact = cube.apply(&BigUint::from(old)) % modulus;
+        assert_eq!(
+            BigUint::from(cube.apply(&Residue::new(old, modulus)).n),
+            exact
+        );
+    }
+}
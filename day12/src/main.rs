@@ -2,6 +2,8 @@
 
 use std::collections::{HashMap, HashSet};
 
+use util::dot::{Dot, DotWriter, Highlight, Kind};
+
 type In = Input;
 type Out = u64;
 
@@ -10,6 +12,57 @@ type Pos = (usize, usize);
 #[derive(Clone)]
 struct Input(HashMap<Pos, u8>, Pos, Pos);
 
+fn node_id((x, y): Pos) -> String {
+    format!("{x},{y}")
+}
+
+impl Dot for Input {
+    const KIND: Kind = Kind::Digraph;
+
+    fn write_dot(&self, w: &mut DotWriter) {
+        let Input(heights, start, goal) = self;
+
+        let mut positions = Vec::from_iter(heights.keys().copied());
+        positions.sort_by_key(|&(x, y)| (y, x));
+
+        w.graph().set("layout", "neato");
+        for &pos in &positions {
+            let label = match pos {
+                _ if pos == *start => 'S',
+                _ if pos == *goal => 'E',
+                _ => heights[&pos] as char,
+            };
+            w.node(node_id(pos))
+                .set("label", label)
+                .set("pos", format!("{},{}!", pos.0, -(pos.1 as i64)))
+                .set("shape", "square");
+        }
+
+        for &(x, y) in &positions {
+            let here = heights[&(x, y)];
+            for dst in [
+                (x + 1, y),
+                (x, y + 1),
+                (x.wrapping_sub(1), y),
+                (x, y.wrapping_sub(1)),
+            ] {
+                if heights.get(&dst).is_some_and(|&h| h <= here + 1) {
+                    w.edge(node_id((x, y)), node_id(dst));
+                }
+            }
+        }
+    }
+}
+
+// Walks `prev` back from `end`, then flips it around to get the route in walking order.
+fn route(prev: &HashMap<Pos, Pos>, end: Pos) -> Vec<String> {
+    let mut route = vec![end];
+    while let Some(&p) = prev.get(route.last().unwrap()) {
+        route.push(p);
+    }
+    route.into_iter().rev().map(node_id).collect()
+}
+
 fn parse(s: &'static str) -> In {
     let mut start = Pos::default();
     let mut goal = Pos::default();
@@ -74,6 +127,7 @@ fn setup(
 fn part1(n: &In) -> Out {
     let (heights, start, goal, dims, mut unvisited, mut distances) = setup(n);
     distances.insert(start, 0);
+    let mut prev = HashMap::new();
 
     loop {
         let current = unvisited.iter().min_by_key(|pos| distances[pos]).unwrap();
@@ -83,11 +137,17 @@ fn part1(n: &In) -> Out {
             if heights[neighbor] <= heights[current] + 1 {
                 let td = distances[current] + 1;
                 let dn = distances.get_mut(neighbor).unwrap();
-                *dn = u64::min(*dn, td);
+                if td < *dn {
+                    *dn = td;
+                    prev.insert(*neighbor, *current);
+                }
             }
         }
 
         if *current == goal {
+            if util::dot::is_enabled() {
+                util::dot::export(n, &Highlight::path(route(&prev, goal))).unwrap();
+            }
             return distances[&current];
         }
 
@@ -98,6 +158,7 @@ fn part1(n: &In) -> Out {
 fn part2(n: &In) -> Out {
     let (heights, _, goal, dims, mut unvisited, mut distances) = setup(n);
     distances.insert(goal, 0);
+    let mut prev = HashMap::new();
 
     loop {
         let current = unvisited.iter().min_by_key(|pos| distances[pos]).unwrap();
@@ -107,11 +168,20 @@ fn part2(n: &In) -> Out {
             if heights[neighbor] >= heights[current] - 1 {
                 let td = distances[current] + 1;
                 let dn = distances.get_mut(neighbor).unwrap();
-                *dn = u64::min(*dn, td);
+                if td < *dn {
+                    *dn = td;
+                    prev.insert(*neighbor, *current);
+                }
             }
         }
 
         if heights[current] == b'a' {
+            if util::dot::is_enabled() {
+                // searched backwards from the goal, so flip the route to climb up from here
+                let mut steps = route(&prev, *current);
+                steps.reverse();
+                util::dot::export(n, &Highlight::path(steps)).unwrap();
+            }
             return distances[current];
         }
 
@@ -1,8 +1,10 @@
 #![cfg_attr(test, feature(test))]
 
 use std::cmp::Ordering;
+use std::fmt::{self, Display};
+use std::str::FromStr;
 
-#[derive(Clone, PartialEq, Ord, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 enum Thing {
     One(u32),
     Many(Vec<Thing>),
@@ -29,70 +31,257 @@ impl Thing {
 impl std::fmt::Debug for Thing {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Thing::One(a) => a.fmt(f),
-            Thing::Many(v) => v.fmt(f),
+            Thing::One(a) => fmt::Debug::fmt(a, f),
+            Thing::Many(v) => fmt::Debug::fmt(v, f),
         }
     }
 }
 
+impl Display for Thing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Thing::One(a) => write!(f, "{a}"),
+            Thing::Many(v) => {
+                write!(f, "[")?;
+                for (i, t) in v.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{t}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl Thing {
+    // The puzzle's ordering, under which `1` and `[1]` are equal.
+    fn packet_cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::One(n), Self::One(m)) => n.cmp(m),
+            (Self::Many(v1), Self::Many(v2)) => {
+                let mut items = v1.iter().zip(v2).map(|(a, b)| a.packet_cmp(b));
+                items
+                    .find(|o| o.is_ne())
+                    .unwrap_or_else(|| v1.len().cmp(&v2.len()))
+            }
+            (Self::One(a), v @ Self::Many(_)) => Self::just_one(*a).packet_cmp(v),
+            (v @ Self::Many(_), Self::One(a)) => v.packet_cmp(&Self::just_one(*a)),
+        }
+    }
+
+    // Breaks the ties `packet_cmp` leaves, so that only identical things compare equal.
+    fn shape_cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::One(n), Self::One(m)) => n.cmp(m),
+            (Self::Many(v1), Self::Many(v2)) => {
+                let mut items = v1.iter().zip(v2).map(|(a, b)| a.shape_cmp(b));
+                items
+                    .find(|o| o.is_ne())
+                    .unwrap_or_else(|| v1.len().cmp(&v2.len()))
+            }
+            (Self::One(_), Self::Many(_)) => Ordering::Less,
+            (Self::Many(_), Self::One(_)) => Ordering::Greater,
+        }
+    }
+}
+
+impl Ord for Thing {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.packet_cmp(other).then_with(|| self.shape_cmp(other))
+    }
+}
+
 impl PartialOrd for Thing {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match (self, other) {
-            (Self::One(n), Self::One(m)) => n.partial_cmp(m),
-            (Self::Many(v1), Self::Many(v2)) => v1.partial_cmp(v2),
-            (Self::One(a), v @ Self::Many(_)) => Self::partial_cmp(&Self::just_one(*a), v),
-            (v @ Self::Many(_), Self::One(a)) => Self::partial_cmp(v, &Self::just_one(*a)),
+        Some(self.cmp(other))
+    }
+}
+
+// Where two packets first disagree: indices into nested lists, with a number
+// standing in for a one-element list where it gets compared against one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Difference {
+    path: Vec<usize>,
+    ordering: Ordering,
+    reason: String,
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in &self.path {
+            write!(f, "[{i}]")?;
+        }
+        if self.path.is_empty() {
+            write!(f, "top level")?;
         }
+        write!(f, ": {} ({:?})", self.reason, self.ordering)
     }
 }
 
-type In = Option<Thing>;
-type Out = usize;
+// Why `a` and `b` are in the order they are, or None if the puzzle's ordering can't tell them
+// apart.
+fn explain(a: &Thing, b: &Thing) -> Option<Difference> {
+    let mut path = Vec::new();
+    let ordering = explain_at(a, b, &mut path)?;
+    let reason = describe(a, b, &path, ordering);
+    Some(Difference {
+        path,
+        ordering,
+        reason,
+    })
+}
+
+fn explain_at(a: &Thing, b: &Thing, path: &mut Vec<usize>) -> Option<Ordering> {
+    match (a, b) {
+        (Thing::One(n), Thing::One(m)) => Some(n.cmp(m)).filter(|o| o.is_ne()),
+        (Thing::Many(v1), Thing::Many(v2)) => {
+            for (i, (x, y)) in v1.iter().zip(v2).enumerate() {
+                path.push(i);
+                if let Some(o) = explain_at(x, y, path) {
+                    return Some(o);
+                }
+                path.pop();
+            }
+            Some(v1.len().cmp(&v2.len())).filter(|o| o.is_ne())
+        }
+        (Thing::One(n), v @ Thing::Many(_)) => explain_at(&Thing::just_one(*n), v, path),
+        (v @ Thing::Many(_), Thing::One(n)) => explain_at(v, &Thing::just_one(*n), path),
+    }
+}
 
-fn parse_packet(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Thing {
-    match chars.next().unwrap() {
-        '[' => {
+fn lookup<'a>(thing: &'a Thing, path: &[usize]) -> Option<&'a Thing> {
+    let Some((&i, rest)) = path.split_first() else {
+        return Some(thing);
+    };
+    match thing {
+        Thing::Many(v) => lookup(v.get(i)?, rest),
+        // a number promoted to `[n]` only has index 0
+        Thing::One(_) if i == 0 => lookup(thing, rest),
+        Thing::One(_) => None,
+    }
+}
+
+fn describe(a: &Thing, b: &Thing, path: &[usize], ordering: Ordering) -> String {
+    match (lookup(a, path), lookup(b, path)) {
+        (Some(x @ Thing::One(_)), Some(y @ Thing::One(_))) => format!("{x} vs {y}"),
+        _ if ordering.is_lt() => String::from("left list runs out first"),
+        _ => String::from("right list runs out first"),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseError {
+    pos: usize,
+    expected: &'static str,
+    found: Option<char>,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.found {
+            Some(c) => write!(
+                f,
+                "at {}: expected {}, found {c:?}",
+                self.pos, self.expected
+            ),
+            None => write!(
+                f,
+                "at {}: expected {}, found end of input",
+                self.pos, self.expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+type Chars<'a> = std::iter::Peekable<std::str::CharIndices<'a>>;
+
+fn error(chars: &mut Chars<'_>, len: usize, expected: &'static str) -> ParseError {
+    let (pos, found) = match chars.peek() {
+        Some(&(pos, c)) => (pos, Some(c)),
+        None => (len, None),
+    };
+    ParseError {
+        pos,
+        expected,
+        found,
+    }
+}
+
+fn parse_packet(chars: &mut Chars<'_>, len: usize) -> Result<Thing, ParseError> {
+    match chars.peek().map(|&(_, c)| c) {
+        Some('[') => {
+            chars.next();
             let mut things = vec![];
-            if chars.peek() == Some(&']') {
-                return Thing::Many(things);
+            if let Some((_, ']')) = chars.peek() {
+                chars.next();
+                return Ok(Thing::Many(things));
             }
 
             loop {
-                things.push(parse_packet(chars));
-                match chars.next() {
-                    Some(']') => return Thing::Many(things),
-                    Some(',') => continue,
-                    c => panic!("oh nyo: {c:?}"),
+                things.push(parse_packet(chars, len)?);
+                match chars.peek() {
+                    Some((_, ']')) => {
+                        chars.next();
+                        return Ok(Thing::Many(things));
+                    }
+                    Some((_, ',')) => {
+                        chars.next();
+                    }
+                    _ => return Err(error(chars, len, "',' or ']'")),
                 }
             }
         }
-        c @ '0'..='9' => {
-            let mut n = c.to_digit(10).unwrap();
-            while let Some(c @ '0'..='9') = chars.peek().copied() {
-                n = 10 * n + c.to_digit(10).unwrap();
+        Some('0'..='9') => {
+            let mut n: u32 = 0;
+            while let Some(&(_, c @ '0'..='9')) = chars.peek() {
+                n = n
+                    .checked_mul(10)
+                    .and_then(|n| n.checked_add(c.to_digit(10).unwrap()))
+                    .ok_or_else(|| error(chars, len, "a smaller number"))?;
                 chars.next();
             }
-            Thing::One(n)
+            Ok(Thing::One(n))
         }
-        c => panic!("oh no: {c:?}"),
+        _ => Err(error(chars, len, "'[' or a number")),
     }
 }
 
+impl FromStr for Thing {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.char_indices().peekable();
+        let thing = parse_packet(&mut chars, s.len())?;
+        match chars.peek() {
+            None => Ok(thing),
+            Some(_) => Err(error(&mut chars, s.len(), "end of input")),
+        }
+    }
+}
+
+type In = Option<Thing>;
+type Out = usize;
+
 fn parse(s: &'static str) -> In {
     if s.is_empty() {
         return None;
     }
 
-    Some(parse_packet(&mut s.chars().peekable()))
+    Some(s.parse().unwrap_or_else(|e| panic!("{e} in {s:?}")))
 }
 
 fn part1(packets: &[In]) -> Out {
     let mut sum = 0;
 
     for i in 0..(packets.len() / 3) {
-        let a = &packets[i * 3];
-        let b = &packets[i * 3 + 1];
-        if a <= b {
+        let a = packets[i * 3].as_ref().unwrap();
+        let b = packets[i * 3 + 1].as_ref().unwrap();
+        // packets the puzzle's ordering calls equal are in order too
+        if explain(a, b).is_none_or(|d| d.ordering.is_lt()) {
             sum += i + 1;
         }
     }
@@ -116,3 +305,121 @@ fn part2(packets: &[In]) -> Out {
 }
 
 util::register!(parse, part1, part2);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thing(s: &str) -> Thing {
+        s.parse().unwrap()
+    }
+
+    const SAMPLES: &[&str] = &[
+        "[]",
+        "[[]]",
+        "[[[]]]",
+        "1",
+        "[1]",
+        "[[1]]",
+        "[1,1]",
+        "[[1],1]",
+        "[1,[1]]",
+        "[2]",
+        "[1,2,3]",
+        "[[1,2],3]",
+        "[[4,4],4,4]",
+        "[[4,4],4,4,4]",
+        "[10,[0,[]]]",
+        "[4294967295]",
+    ];
+
+    #[test]
+    fn prints_what_it_parses() {
+        for s in SAMPLES {
+            assert_eq!(thing(s).to_string(), *s);
+        }
+        assert_eq!(thing("[007,[]]").to_string(), "[7,[]]");
+    }
+
+    #[test]
+    fn reports_where_parsing_fails() {
+        let cases = [
+            ("", 0, "'[' or a number", None),
+            ("[1,2", 4, "',' or ']'", None),
+            ("[1;2]", 2, "',' or ']'", Some(';')),
+            ("[,]", 1, "'[' or a number", Some(',')),
+            ("[1]]", 3, "end of input", Some(']')),
+            ("[ 1]", 1, "'[' or a number", Some(' ')),
+            ("[4294967296]", 10, "a smaller number", Some('6')),
+        ];
+        for (s, pos, expected, found) in cases {
+            let err = s.parse::<Thing>().unwrap_err();
+            let want = ParseError {
+                pos,
+                expected,
+                found,
+            };
+            assert_eq!(err, want, "{s:?}");
+        }
+        let err = "[1,2".parse::<Thing>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at 4: expected ',' or ']', found end of input"
+        );
+    }
+
+    #[test]
+    fn ordering_is_total_and_agrees_with_eq() {
+        let things = Vec::from_iter(SAMPLES.iter().map(|s| thing(s)));
+        for a in &things {
+            for b in &things {
+                assert_eq!(a.cmp(b), b.cmp(a).reverse(), "{a} vs {b}");
+                assert_eq!(a.cmp(b).is_eq(), a == b, "{a} vs {b}");
+                // the tie-break never overrules the puzzle's ordering
+                if a.packet_cmp(b).is_ne() {
+                    assert_eq!(a.cmp(b), a.packet_cmp(b), "{a} vs {b}");
+                }
+                for c in &things {
+                    if a <= b && b <= c {
+                        assert!(a <= c, "{a} <= {b} <= {c}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn breaks_the_puzzles_ties() {
+        let (a, b) = (thing("1"), thing("[[1]]"));
+        assert_eq!(a.packet_cmp(&b), Ordering::Equal);
+        assert_eq!(a.cmp(&b), Ordering::Less);
+        assert_ne!(a, b);
+        let mut sorted = vec![thing("[[1]]"), thing("[1]"), thing("1")];
+        sorted.sort();
+        assert_eq!(sorted, [thing("1"), thing("[1]"), thing("[[1]]")]);
+    }
+
+    #[test]
+    fn explains_differences() {
+        let d = explain(&thing("[1,[2,3]]"), &thing("[1,[2,4]]")).unwrap();
+        assert_eq!(d.path, [1, 1]);
+        assert_eq!(d.ordering, Ordering::Less);
+        assert_eq!(d.to_string(), "[1][1]: 3 vs 4 (Less)");
+
+        let d = explain(&thing("[[4,4],4,4,4]"), &thing("[[4,4],4,4]")).unwrap();
+        assert_eq!(
+            d.to_string(),
+            "top level: right list runs out first (Greater)"
+        );
+
+        // 9 is promoted to [9] to meet [[8,7,6]]
+        let d = explain(&thing("[9]"), &thing("[[8,7,6]]")).unwrap();
+        assert_eq!(d.to_string(), "[0][0]: 9 vs 8 (Greater)");
+
+        let d = explain(&thing("[[]]"), &thing("[[3]]")).unwrap();
+        assert_eq!(d.to_string(), "[0]: left list runs out first (Less)");
+
+        assert_eq!(explain(&thing("[1]"), &thing("1")), None);
+        assert_eq!(explain(&thing("[]"), &thing("[]")), None);
+    }
+}
@@ -1,8 +1,11 @@
 #![cfg_attr(test, feature(test))]
 
-use std::collections::HashMap;
+use std::fmt::{self, Display};
 
-#[derive(Hash, PartialEq, Eq, Copy, Clone)]
+use util::record::{Frame, Palette, Recorder};
+use util::sim::{hash_of, Simulation};
+
+#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
 struct Pos {
     x: u32,
     y: u32,
@@ -21,103 +24,329 @@ fn parse(s: &'static str) -> In {
         .collect()
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 enum Tile {
+    Air,
     Wall,
     Sand,
 }
 
-fn build_world(paths: &[Vec<Pos>]) -> HashMap<Pos, Tile> {
-    let mut world = HashMap::new();
+#[derive(Copy, Clone)]
+struct Params {
+    source: Pos,
+    // how far below the lowest wall the floor is, if there is one
+    floor: Option<u32>,
+}
 
-    for path in paths {
-        for win in path.windows(2) {
-            let start = win[0];
-            let end = win[1];
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CaveError {
+    NoWalls,
+    // the floor has to be at least one below the lowest wall
+    FloorTooHigh(u32),
+    // sand could spread further than a u32 coordinate reaches
+    OutOfRange,
+}
 
-            let x0 = start.x.min(end.x);
-            let x1 = start.x.max(end.x);
-            let y0 = start.y.min(end.y);
-            let y1 = start.y.max(end.y);
-            if x0 == x1 {
-                let x = x0;
-                for y in y0..=y1 {
-                    world.insert(Pos { x, y }, Tile::Wall);
-                }
-            } else {
-                assert_eq!(y0, y1);
-                let y = y0;
-                for x in x0..=x1 {
-                    world.insert(Pos { x, y }, Tile::Wall);
-                }
-            }
+impl Display for CaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoWalls => write!(f, "no walls to pile sand on"),
+            Self::FloorTooHigh(d) => write!(f, "a floor {d} below the lowest wall is in the walls"),
+            Self::OutOfRange => write!(f, "the pile would spread past the edge of the coordinates"),
         }
     }
+}
+
+impl std::error::Error for CaveError {}
 
-    world
+// A dense grid just wide enough for any pile the source could make.
+struct Grid {
+    x0: u32,
+    width: usize,
+    tiles: Vec<Tile>,
 }
 
-fn fall(world: &HashMap<Pos, Tile>, bottom: u32) -> Pos {
-    let mut x = 500;
-    let mut y = 0;
-    loop {
-        if !world.contains_key(&Pos { x, y: y + 1 }) {
-            y += 1;
-        } else if !world.contains_key(&Pos { x: x - 1, y: y + 1 }) {
-            x -= 1;
-            y += 1;
-        } else if !world.contains_key(&Pos { x: x + 1, y: y + 1 }) {
-            x += 1;
-            y += 1;
-        } else {
-            break;
-        }
+impl Grid {
+    // A grain spreads at most one column per row, and looks one column further either side,
+    // so `height` columns either side of the source cover everything it can reach.
+    fn new(paths: &[Vec<Pos>], params: Params, height: u32) -> Result<Self, CaveError> {
+        let walls = paths.iter().flatten();
+        let x0 = walls.clone().map(|p| p.x).min().ok_or(CaveError::NoWalls)?;
+        let x1 = walls.map(|p| p.x).max().ok_or(CaveError::NoWalls)?;
+        let left = params.source.x.checked_sub(height);
+        let right = params.source.x.checked_add(height);
+        let x0 = x0.min(left.ok_or(CaveError::OutOfRange)?);
+        let x1 = x1.max(right.ok_or(CaveError::OutOfRange)?);
+        let width = (x1 - x0 + 1) as usize;
+
+        let mut grid = Self {
+            x0,
+            width,
+            tiles: vec![Tile::Air; width * height as usize],
+        };
 
-        if y >= bottom {
-            break;
+        for path in paths {
+            for win in path.windows(2) {
+                let (start, end) = (win[0], win[1]);
+                for x in start.x.min(end.x)..=start.x.max(end.x) {
+                    for y in start.y.min(end.y)..=start.y.max(end.y) {
+                        grid.set(Pos { x, y }, Tile::Wall);
+                    }
+                }
+            }
         }
+
+        Ok(grid)
+    }
+
+    fn index(&self, pos: Pos) -> usize {
+        let x = pos.x.checked_sub(self.x0).map(|x| x as usize);
+        let x = x
+            .filter(|&x| x < self.width)
+            .expect("off the side of the grid");
+        pos.y as usize * self.width + x
     }
 
-    Pos { x, y }
+    fn get(&self, pos: Pos) -> Tile {
+        self.tiles[self.index(pos)]
+    }
+
+    fn set(&mut self, pos: Pos, tile: Tile) {
+        let i = self.index(pos);
+        self.tiles[i] = tile;
+    }
+
+    fn height(&self) -> u32 {
+        (self.tiles.len() / self.width) as u32
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (Pos, Tile)> + '_ {
+        self.tiles.iter().enumerate().map(|(i, &t)| {
+            let (x, y) = (i % self.width, i / self.width);
+            let pos = Pos {
+                x: self.x0 + x as u32,
+                y: y as u32,
+            };
+            (pos, t)
+        })
+    }
 }
 
-fn part1(n: &[In]) -> Out {
-    let mut world = build_world(n);
-    let bottom = world.keys().map(|p| p.y).max().unwrap();
+fn palette() -> Palette {
+    Palette::new(&[[16, 16, 32], [128, 128, 128], [230, 200, 90]])
+}
 
-    let mut ngrains = 0;
+fn draw(grid: &Grid, source: Pos) -> Frame {
+    let solid = Vec::from_iter(grid.cells().filter(|(_, t)| *t != Tile::Air));
+    let points = solid.iter().map(|(p, _)| (p.x as i64, p.y as i64));
+    let mut frame = Frame::from_points(points.chain([(source.x as i64, source.y as i64)]), 0);
+    for (pos, tile) in solid {
+        let color = match tile {
+            Tile::Air => 0,
+            Tile::Wall => 1,
+            Tile::Sand => 2,
+        };
+        frame.set(pos.x as i64, pos.y as i64, color);
+    }
+    frame
+}
 
-    loop {
-        let pos = fall(&world, bottom);
+// Each grain starts where the one before it was last still moving, so the path
+// down from the source is a stack that only ever gets popped to the last fork.
+struct Cave {
+    grid: Grid,
+    params: Params,
+    // lowest row a grain can be in without falling forever, or resting on the floor
+    bottom: u32,
+    path: Vec<Pos>,
+    ngrains: usize,
+    done: bool,
+    rec: Recorder,
+}
 
-        if pos.y >= bottom {
-            break;
-        } else {
-            ngrains += 1;
-            world.insert(pos, Tile::Sand);
-        }
+impl Cave {
+    fn new(n: &[In], params: Params, rec: Recorder) -> Result<Self, CaveError> {
+        let lowest = n
+            .iter()
+            .flatten()
+            .map(|p| p.y)
+            .max()
+            .ok_or(CaveError::NoWalls)?;
+        let bottom = match params.floor {
+            Some(0) => return Err(CaveError::FloorTooHigh(0)),
+            Some(d) => lowest.checked_add(d - 1),
+            None => Some(lowest),
+        };
+        let bottom = bottom.ok_or(CaveError::OutOfRange)?;
+        let height = bottom.checked_add(2).ok_or(CaveError::OutOfRange)?;
+        let grid = Grid::new(n, params, height)?;
+        Ok(Self {
+            grid,
+            params,
+            bottom,
+            path: vec![params.source],
+            ngrains: 0,
+            done: false,
+            rec,
+        })
     }
 
-    ngrains
+    fn finish_recording(mut self) {
+        self.rec.keyframe(|| draw(&self.grid, self.params.source));
+        self.rec.finish().unwrap();
+    }
 }
 
-fn part2(n: &[In]) -> Out {
-    let mut world = build_world(n);
-    let bottom = world.keys().map(|p| p.y).max().unwrap() + 1;
+impl Simulation for Cave {
+    fn step(&mut self) {
+        self.rec.frame(|| draw(&self.grid, self.params.source));
+
+        loop {
+            let Some(&pos) = self.path.last() else {
+                // the source is buried
+                self.done = true;
+                return;
+            };
+
+            if pos.y >= self.bottom {
+                if self.params.floor.is_none() {
+                    self.done = true;
+                    return;
+                }
+            } else {
+                let below = pos.y + 1;
+                let next = [pos.x, pos.x - 1, pos.x + 1]
+                    .map(|x| Pos { x, y: below })
+                    .into_iter()
+                    .find(|&p| self.grid.get(p) == Tile::Air);
+                if let Some(next) = next {
+                    self.path.push(next);
+                    continue;
+                }
+            }
 
-    let mut ngrains = 0;
+            self.grid.set(pos, Tile::Sand);
+            self.path.pop();
+            self.ngrains += 1;
+            self.done = self.path.is_empty();
+            return;
+        }
+    }
 
-    loop {
-        let pos = fall(&world, bottom);
+    fn is_done(&self) -> bool {
+        self.done
+    }
 
-        ngrains += 1;
-        world.insert(pos, Tile::Sand);
+    fn render(&self) -> String {
+        let solid = || {
+            self.grid
+                .cells()
+                .filter(|(_, t)| *t != Tile::Air)
+                .map(|(p, _)| p.x)
+        };
+        let source = self.params.source;
+        let x0 = solid().min().unwrap().min(source.x);
+        let x1 = solid().max().unwrap().max(source.x);
 
-        if pos == (Pos { x: 500, y: 0 }) {
-            break;
+        let mut s = String::new();
+        for y in 0..=self.bottom.min(self.grid.height() - 1) {
+            for x in x0..=x1 {
+                let pos = Pos { x, y };
+                let c = match self.grid.get(pos) {
+                    Tile::Wall => '#',
+                    Tile::Sand => 'o',
+                    Tile::Air if pos == source => '+',
+                    Tile::Air if self.path.contains(&pos) => '~',
+                    Tile::Air => '.',
+                };
+                s.push(c);
+            }
+            s.push('\n');
         }
+        s += &format!("{} grains", self.ngrains);
+        s
+    }
+
+    fn fingerprint(&self) -> u64 {
+        hash_of(&(&self.grid.tiles, &self.path, self.done))
     }
+}
 
+fn fill(n: &[In], params: Params, rec: Recorder) -> Out {
+    let mut cave = Cave::new(n, params, rec).unwrap_or_else(|e| panic!("{e}"));
+    util::sim::run(&mut cave);
+    let ngrains = cave.ngrains;
+    cave.finish_recording();
     ngrains
 }
 
+const SOURCE: Pos = Pos { x: 500, y: 0 };
+
+fn part1(n: &[In]) -> Out {
+    let params = Params {
+        source: SOURCE,
+        floor: None,
+    };
+    fill(n, params, Recorder::new(palette()).every(10))
+}
+
+fn part2(n: &[In]) -> Out {
+    let params = Params {
+        source: SOURCE,
+        floor: Some(2),
+    };
+    fill(n, params, Recorder::new(palette()).every(250))
+}
+
 util::register!(parse, part1, part2);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cave(walls: &[(u32, u32)], source: u32, floor: Option<u32>) -> Result<Cave, CaveError> {
+        let path = Vec::from_iter(walls.iter().map(|&(x, y)| Pos { x, y }));
+        let params = Params {
+            source: Pos { x: source, y: 0 },
+            floor,
+        };
+        Cave::new(&[path], params, Recorder::disabled())
+    }
+
+    #[test]
+    fn fills_to_the_floor() {
+        // four rows of sand above the floor, less the three cells the wall takes
+        let mut cave = cave(&[(9, 3), (11, 3)], 10, Some(1)).unwrap();
+        util::sim::run(&mut cave);
+        assert_eq!(cave.ngrains, 1 + 3 + 5 + 7 - 3);
+    }
+
+    #[test]
+    fn rejects_bad_caves() {
+        assert_eq!(
+            cave(&[(5, 3)], 5, Some(0)).err(),
+            Some(CaveError::FloorTooHigh(0))
+        );
+        assert_eq!(cave(&[], 5, None).err(), Some(CaveError::NoWalls));
+        // the pile under a source at x = 1 would spread past x = 0
+        assert_eq!(
+            cave(&[(0, 3), (2, 3)], 1, None).err(),
+            Some(CaveError::OutOfRange)
+        );
+        assert_eq!(
+            cave(&[(5, u32::MAX - 1)], 5, Some(5)).err(),
+            Some(CaveError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn draws_from_the_source() {
+        // the frame reaches up to the source even with nothing fallen yet
+        let cave = cave(&[(9, 3), (11, 3)], 10, None).unwrap();
+        let mut want = Frame::new(9, 0, 11, 3);
+        for x in 9..=11 {
+            want.set(x, 3, 1);
+        }
+        assert_eq!(draw(&cave.grid, cave.params.source), want);
+    }
+}
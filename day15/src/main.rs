@@ -1,7 +1,8 @@
 #![cfg_attr(test, feature(test))]
 
-use std::collections::{BTreeMap, HashSet};
+use std::collections::HashSet;
 
+use util::svg::{Style, Svg};
 use util::*;
 
 type N = i64;
@@ -41,7 +42,58 @@ fn parse(s: &'static str) -> In {
     Pair { sensor, beacon }
 }
 
-fn part1(n: &[In]) -> Out {
+fn drawing(pairs: &[In]) -> Svg {
+    let mut svg = Svg::new();
+    let f = |p: P| (p.x as f64, p.y as f64);
+
+    for pair in pairs {
+        let (x, y) = f(pair.sensor);
+        let r = pair.radius() as f64;
+        let diamond = [(x, y - r), (x + r, y), (x, y + r), (x - r, y)];
+        let style = Style::new()
+            .fill("steelblue")
+            .opacity(0.25)
+            .stroke("steelblue", 1.0);
+        svg.polygon(diamond, style);
+        svg.polyline(
+            [f(pair.sensor), f(pair.beacon)],
+            Style::new().stroke("gray", 1.0),
+        );
+    }
+
+    for pair in pairs {
+        let (x, y) = f(pair.sensor);
+        svg.label(x, y, "S", Style::new().fill("navy"));
+        let (x, y) = f(pair.beacon);
+        svg.label(x, y, "B", Style::new().fill("darkgreen"));
+    }
+
+    svg
+}
+
+// Which row part 1 counts along, and the box part 2 searches. The example asks about a
+// smaller row and box than the real input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Params {
+    row: N,
+    area: SearchBox,
+}
+
+impl Params {
+    const PUZZLE: Self = Self {
+        row: 2000000,
+        area: SearchBox { lo: 0, hi: 4000000 },
+    };
+
+    #[cfg(test)]
+    const EXAMPLE: Self = Self {
+        row: 10,
+        area: SearchBox { lo: 0, hi: 20 },
+    };
+}
+
+// How many cells in row `y` can't hold a beacon.
+fn no_beacon_count(n: &[In], y: N) -> Out {
     let beacons = n.iter().map(|pair| pair.beacon).collect::<HashSet<_>>();
 
     let min_x = n
@@ -58,8 +110,6 @@ fn part1(n: &[In]) -> Out {
 
     let mut num_hashes = 0;
 
-    let y = if n.len() == 14 { 10 } else { 2000000 };
-
     for x in min_x..=max_x {
         let sample = Pos { x, y };
 
@@ -75,75 +125,319 @@ fn part1(n: &[In]) -> Out {
         }
     }
 
+    if util::svg::is_enabled() {
+        let mut svg = drawing(n);
+        let row = [(min_x as f64, y as f64), (max_x as f64, y as f64)];
+        svg.polyline(row, Style::new().stroke("red", 1.0));
+        util::svg::export(&svg).unwrap();
+    }
+
     num_hashes
 }
 
-fn part2(pairs: &[In]) -> Out {
-    let mut edge_points = BTreeMap::<P, u32>::new();
-    let mut true_candidates = Vec::<P>::new();
+fn part1(n: &[In], params: &Params) -> Out {
+    no_beacon_count(n, params.row)
+}
 
-    let acceptable = if pairs.len() == 14 {
-        0..=20
-    } else {
-        0..=4000000
-    };
+// In rotated coordinates u = x + y, v = x - y, every sensor's range is an axis-aligned square.
+fn rotate(p: P) -> (N, N) {
+    (p.x + p.y, p.x - p.y)
+}
+
+// Only half the (u, v) lattice is a cell; the rest sits between cells.
+fn unrotate(u: N, v: N) -> Option<P> {
+    ((u + v) % 2 == 0).then(|| P::new((u + v) / 2, (u - v) / 2))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Rect {
+    u0: N,
+    u1: N,
+    v0: N,
+    v1: N,
+}
+
+impl Rect {
+    fn of(pair: &Pair) -> Self {
+        let (u, v) = rotate(pair.sensor);
+        let r = pair.radius();
+        Self {
+            u0: u - r,
+            u1: u + r,
+            v0: v - r,
+            v1: v + r,
+        }
+    }
 
-    let mut add_point = |x, y| {
-        if !acceptable.contains(&x) || !acceptable.contains(&y) {
-            return;
+    // What's left of `self` with `hole` cut out, in up to four pieces.
+    fn minus(self, hole: Rect) -> Vec<Rect> {
+        if hole.u0 > self.u1 || hole.u1 < self.u0 || hole.v0 > self.v1 || hole.v1 < self.v0 {
+            return vec![self];
         }
-        let pt = P::new(x, y);
-        let ent = edge_points.entry(pt).or_default();
-        *ent += 1;
-        if *ent > 2 {
-            true_candidates.push(pt);
+        let mut pieces = Vec::new();
+        if self.u0 < hole.u0 {
+            pieces.push(Rect {
+                u1: hole.u0 - 1,
+                ..self
+            });
         }
+        if self.u1 > hole.u1 {
+            pieces.push(Rect {
+                u0: hole.u1 + 1,
+                ..self
+            });
+        }
+        let middle = Rect {
+            u0: self.u0.max(hole.u0),
+            u1: self.u1.min(hole.u1),
+            ..self
+        };
+        if self.v0 < hole.v0 {
+            pieces.push(Rect {
+                v1: hole.v0 - 1,
+                ..middle
+            });
+        }
+        if self.v1 > hole.v1 {
+            pieces.push(Rect {
+                v0: hole.v1 + 1,
+                ..middle
+            });
+        }
+        pieces
+    }
+
+    // The smallest rect around the part of `self` inside `area`, if any of it is. In (u, v)
+    // the box is a diamond: u + v = 2x and u - v = 2y both run from 2 lo to 2 hi.
+    fn clip(self, area: SearchBox) -> Option<Rect> {
+        let (lo, hi) = (2 * area.lo, 2 * area.hi);
+        let Rect { u0, u1, v0, v1 } = self;
+        let r = Rect {
+            u0: u0.max(lo).max(lo - v1).max(lo + v0),
+            u1: u1.min(hi).min(hi - v0).min(hi + v1),
+            v0: v0.max((lo - hi) / 2).max(lo - u1).max(u0 - hi),
+            v1: v1.min((hi - lo) / 2).min(hi - u0).min(u1 - lo),
+        };
+        (r.u0 <= r.u1 && r.v0 <= r.v1).then_some(r)
+    }
+}
+
+// The square of cells with both coordinates in `lo..=hi`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct SearchBox {
+    lo: N,
+    hi: N,
+}
+
+impl SearchBox {
+    fn contains(&self, p: P) -> bool {
+        (self.lo..=self.hi).contains(&p.x) && (self.lo..=self.hi).contains(&p.y)
+    }
+}
+
+fn covered(pairs: &[Pair], p: P) -> bool {
+    pairs.iter().any(|pair| pair.in_range(p))
+}
+
+// Every cell in the box no sensor can see, in (x, y) order. It's exhaustive, so only quick
+// while little of the box is uncovered; neither part needs it.
+#[allow(dead_code)]
+fn uncovered(pairs: &[Pair], area: SearchBox) -> Vec<P> {
+    let SearchBox { lo, hi } = area;
+    let square = Rect {
+        u0: 2 * lo,
+        u1: 2 * hi,
+        v0: lo - hi,
+        v1: hi - lo,
     };
+    // the square's corners, and the pieces cut from them, stick out past the box's diagonal
+    // sides, so each piece is trimmed before it's cut again
+    let mut rects = Vec::from_iter(square.clip(area));
+    for pair in pairs {
+        let hole = Rect::of(pair);
+        let pieces = rects.into_iter().flat_map(|r| r.minus(hole));
+        rects = pieces.filter_map(|r| r.clip(area)).collect();
+    }
 
+    let mut cells = Vec::new();
+    for r in rects {
+        for u in r.u0..=r.u1 {
+            for v in r.v0..=r.v1 {
+                if let Some(p) = unrotate(u, v).filter(|&p| area.contains(p)) {
+                    cells.push(p);
+                }
+            }
+        }
+    }
+    cells.sort_by_key(|p| (p.x, p.y));
+    cells
+}
+
+// A lone gap is hemmed in by sensor ranges, or by the edge of the box, so it's where a line
+// just outside one diamond crosses a line just outside another, or crosses the edge, or it's
+// a corner. A gap wider than a cell may have none of those uncovered, and gives None.
+fn find_gap(pairs: &[Pair], area: SearchBox) -> Option<P> {
+    let mut us = Vec::new();
+    let mut vs = Vec::new();
     for pair in pairs {
-        let r = pair.radius() + 1;
+        let Rect { u0, u1, v0, v1 } = Rect::of(pair);
+        us.extend([u0 - 1, u1 + 1]);
+        vs.extend([v0 - 1, v1 + 1]);
+    }
 
-        let x0 = pair.sensor.x;
-        let y0 = pair.sensor.y;
+    let SearchBox { lo, hi } = area;
+    let mut candidates = Vec::new();
+    for e in [lo, hi] {
+        candidates.extend([lo, hi].map(|f| P::new(e, f)));
+        candidates.extend(
+            us.iter()
+                .flat_map(|&u| [P::new(e, u - e), P::new(u - e, e)]),
+        );
+        candidates.extend(
+            vs.iter()
+                .flat_map(|&v| [P::new(e, e - v), P::new(v + e, e)]),
+        );
+    }
+    candidates.extend(
+        us.iter()
+            .flat_map(|&u| vs.iter().filter_map(move |&v| unrotate(u, v))),
+    );
 
-        let mut dy = -r;
-        let mut dx = 0;
+    candidates
+        .into_iter()
+        .filter(|&p| area.contains(p) && !covered(pairs, p))
+        .min_by_key(|p| (p.x, p.y))
+}
 
-        // north -> east
-        while dy != 0 {
-            add_point(x0 + dx, y0 + dy);
-            dy += 1;
-            dx += 1;
-        }
+fn tuning_frequency(pairs: &[In], area: SearchBox) -> Option<Out> {
+    let beacon = find_gap(pairs, area)?;
+
+    if util::svg::is_enabled() {
+        let mut svg = drawing(pairs);
+        let (lo, hi) = (area.lo as f64, area.hi as f64);
+        svg.rect(lo, lo, hi - lo, hi - lo, Style::new().stroke("black", 1.0));
+        let (x, y) = (beacon.x as f64, beacon.y as f64);
+        svg.label(
+            x,
+            y,
+            format!("gap ({}, {})", beacon.x, beacon.y),
+            Style::new().fill("red"),
+        );
+        util::svg::export(&svg).unwrap();
+    }
 
-        // east -> south
-        while dx != 0 {
-            add_point(x0 + dx, y0 + dy);
-            dy += 1;
-            dx -= 1;
-        }
+    Some(beacon.x * 4000000 + beacon.y)
+}
+
+fn part2(pairs: &[In], params: &Params) -> Out {
+    tuning_frequency(pairs, params.area).expect("no lone gap in the box")
+}
+
+util::register!(parse, part1, part2, @params(Params::PUZZLE, Params::EXAMPLE));
 
-        // south -> west
-        while dy != 0 {
-            add_point(x0 + dx, y0 + dy);
-            dy -= 1;
-            dx -= 1;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(sensor: (N, N), radius: N) -> Pair {
+        let sensor = P::new(sensor.0, sensor.1);
+        Pair {
+            sensor,
+            beacon: P::new(sensor.x + radius, sensor.y),
         }
+    }
 
-        // west -> north
-        while dx != 0 {
-            add_point(x0 + dx, y0 + dy);
-            dy -= 1;
-            dx += 1;
+    fn brute_force(pairs: &[Pair], area: SearchBox) -> Vec<P> {
+        let cells =
+            (area.lo..=area.hi).flat_map(|x| (area.lo..=area.hi).map(move |y| P::new(x, y)));
+        cells.filter(|&p| !covered(pairs, p)).collect()
+    }
+
+    #[test]
+    fn example_row_and_box() {
+        let pairs = parse_input_lines(TEST_INPUT, parse);
+        assert_eq!(no_beacon_count(&pairs, 10), 26);
+        let area = SearchBox { lo: 0, hi: 20 };
+        assert_eq!(find_gap(&pairs, area), Some(P::new(14, 11)));
+        assert_eq!(tuning_frequency(&pairs, area), Some(56000011));
+        // the same sensors leave plenty uncovered in a box that isn't the example's
+        assert!(uncovered(&pairs, SearchBox { lo: 0, hi: 30 }).len() > 1);
+    }
+
+    #[test]
+    fn uncovered_matches_brute_force() {
+        let area = SearchBox { lo: -3, hi: 9 };
+        let layouts = [
+            vec![],
+            vec![pair((3, 3), 4)],
+            vec![pair((0, 0), 5), pair((9, 9), 6), pair((9, 0), 3)],
+            vec![
+                pair((-5, 3), 9),
+                pair((12, 3), 9),
+                pair((3, -8), 9),
+                pair((3, 14), 8),
+            ],
+        ];
+        for pairs in layouts {
+            assert_eq!(uncovered(&pairs, area), brute_force(&pairs, area));
         }
     }
 
-    let beacon = true_candidates
-        .into_iter()
-        .find(|pos| pairs.iter().all(|pair| !pair.in_range(*pos)))
-        .unwrap();
+    #[test]
+    fn clips_to_the_box() {
+        let area = SearchBox { lo: 0, hi: 4 };
+        // the top corner of the diamond, around (0, 0)
+        let corner = Rect {
+            u0: -10,
+            u1: 1,
+            v0: -10,
+            v1: 10,
+        };
+        assert_eq!(
+            corner.clip(area),
+            Some(Rect {
+                u0: 0,
+                u1: 1,
+                v0: -1,
+                v1: 1
+            })
+        );
+        assert_eq!(
+            Rect {
+                u0: -10,
+                u1: -1,
+                v0: 0,
+                v1: 0
+            }
+            .clip(area),
+            None
+        );
+        // a rect inside the box's bounding square but past its diagonal side
+        assert_eq!(
+            Rect {
+                u0: 0,
+                u1: 1,
+                v0: 3,
+                v1: 4
+            }
+            .clip(area),
+            None
+        );
+    }
 
-    beacon.x * 4000000 + beacon.y
+    #[test]
+    fn finds_gaps_at_the_edges() {
+        let area = SearchBox { lo: 0, hi: 20 };
+        for gap in [P::new(0, 0), P::new(20, 7), P::new(13, 20), P::new(9, 11)] {
+            // cover everything but `gap` with a sensor off each of its corners
+            let pairs = Vec::from_iter([(1, 1), (1, -1), (-1, 1), (-1, -1)].map(|(dx, dy)| {
+                let sensor = (gap.x + dx * 25, gap.y + dy * 25);
+                pair(sensor, 49)
+            }));
+            assert_eq!(brute_force(&pairs, area), [gap]);
+            assert_eq!(find_gap(&pairs, area), Some(gap));
+            assert_eq!(uncovered(&pairs, area), [gap]);
+        }
+        assert_eq!(find_gap(&[pair((10, 10), 20)], area), None);
+    }
 }
-
-util::register!(parse, part1, part2);
@@ -1,42 +1,22 @@
-use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
+use fnv::FnvHashMap as HashMap;
 
-use super::*;
-
-#[derive(PartialEq, Clone)]
-pub struct Keyer {
-    pub(crate) inner: HashMap<&'static str, u64>,
-}
+use util::bitset::Indexer;
+use util::dot::{Dot, DotWriter};
 
-impl Keyer {
-    pub fn get(&self, name: &str) -> u64 {
-        let v = self.inner[name];
-        debug_assert_eq!(v.count_ones(), 1);
-        v
-    }
-
-    fn from_names(names: impl IntoIterator<Item = &'static str>) -> Self {
-        let mut names = Vec::from_iter(names);
-        names.sort();
-        let mut k = 1;
-        let mut inner = HashMap::default();
-        for name in names {
-            inner.insert(name, k);
-            k <<= 1;
-        }
-        Self { inner }
-    }
-}
+use super::*;
 
 #[derive(PartialEq)]
 pub struct Graph {
     pub map: HashMap<&'static str, Node>,
-    pub keyer: Keyer,
+    pub keyer: Indexer<&'static str>,
 }
 
 impl Graph {
     pub fn from_nodes(nodes: &[Node]) -> Self {
         let map = nodes.iter().cloned().map(|n| (n.name, n)).collect();
-        let keyer = Keyer::from_names(nodes.iter().map(|n| n.name));
+        let mut names = Vec::from_iter(nodes.iter().map(|n| n.name));
+        names.sort();
+        let keyer = Indexer::new(names);
         Self { map, keyer }
     }
 
@@ -90,57 +70,15 @@ impl Graph {
     }
 }
 
-impl std::fmt::Display for Graph {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "graph G {{")?;
-
+impl Dot for Graph {
+    fn write_dot(&self, w: &mut DotWriter) {
         for node in self.map.values() {
             let (name, flow) = (node.name, node.flow);
-            writeln!(f, "\t{name} [label=\"{name}\\n{flow}\"];")?;
+            w.node(name).set("label", format!("{name}\n{flow}"));
         }
 
-        let mut done_edges = HashSet::default();
-
         for (src, dst, len) in self.edges() {
-            if done_edges.contains(&(dst, src)) {
-                continue;
-            }
-
-            writeln!(f, "\t{src} -- {dst} [len={len},label={len}];")?;
-            done_edges.insert((src, dst));
-        }
-
-        writeln!(f, "}}")?;
-
-        Ok(())
-    }
-}
-
-pub fn graphviz(n: &[In]) {
-    println!("graph G {{");
-
-    for foo in n {
-        print!("\t{}", foo.name);
-        if foo.name == "AA" {
-            print!(" [label=\"{}\"]", foo.name);
-        } else if foo.flow != 0 {
-            print!(" [label=\"{}\"]", foo.flow);
+            w.edge(src, dst).set("len", len).set("label", len);
         }
-        println!();
-    }
-
-    let mut edges: Vec<([&'static str; 2], N)> = n
-        .iter()
-        .flat_map(|v| v.adjacencies.iter().map(|(dst, len)| ([v.name, dst], *len)))
-        .collect();
-
-    for (edge, _) in &mut edges {
-        edge.sort();
     }
-
-    for ([a, b], n) in HashMap::<_, _>::from_iter(edges) {
-        println!("\t{a} -- {b} [label=\"{n}\"]");
-    }
-
-    println!("}}");
 }
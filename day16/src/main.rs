@@ -4,6 +4,7 @@ use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet};
 
 use rayon::prelude::*;
+use util::dot::Highlight;
 
 type N = u32;
 
@@ -16,6 +17,7 @@ use graph::Graph;
 pub mod state;
 use state::part1::State1;
 use state::part2::State2;
+use state::State;
 
 pub mod node;
 use node::Node;
@@ -23,16 +25,28 @@ use node::Node;
 fn part1(n: &[In]) -> Out {
     let mut graph = Graph::from_nodes(n);
     graph.collapse_edges();
-    solution(State1::new("AA", &graph), 30)
+    let best = solution(State1::new("AA", &graph), 30);
+    export(&graph, &best);
+    best.pressure_released()
 }
 
 fn part2(n: &[In]) -> Out {
     let mut graph = Graph::from_nodes(n);
     graph.collapse_edges();
-    solution(State2::new("AA", &graph), 26)
+    let best = solution(State2::new("AA", &graph), 26);
+    export(&graph, &best);
+    best.pressure_released()
 }
 
-fn solution<S>(initial_state: S, max_time: N) -> N
+fn export(graph: &Graph, best: &impl State) {
+    if !util::dot::is_enabled() {
+        return;
+    }
+    let opened = best.opened_valves().into_iter().chain(["AA"]);
+    util::dot::export(graph, &Highlight::nodes(opened)).unwrap();
+}
+
+fn solution<S>(initial_state: S, max_time: N) -> S
 where
     S: state::State + Send + Sync,
     S::Key: Send + Sync,
@@ -75,9 +89,8 @@ where
     }
 
     finished_states
-        .iter()
-        .map(|s| s.pressure_released())
-        .max()
+        .into_iter()
+        .max_by_key(|s| s.pressure_released())
         .unwrap()
 }
 
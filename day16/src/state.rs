@@ -1,9 +1,9 @@
 pub mod part1;
 pub mod part2;
 
-use crate::graph::Keyer;
 use crate::N;
 use std::cmp::Ordering;
+use util::bitset::BitSet;
 
 pub trait State: PartialOrd + Sized {
     type Key: Ord;
@@ -11,6 +11,7 @@ pub trait State: PartialOrd + Sized {
     fn time_elapsed(&self) -> N;
     fn pressure_released(&self) -> N;
     fn choices(&self, max_time: N) -> Vec<Self>;
+    fn opened_valves(&self) -> Vec<&'static str>;
 }
 
 impl State for part1::State1<'_> {
@@ -31,6 +32,10 @@ impl State for part1::State1<'_> {
     fn choices(&self, max_time: N) -> Vec<Self> {
         self.choices(max_time)
     }
+
+    fn opened_valves(&self) -> Vec<&'static str> {
+        self.opened_valves()
+    }
 }
 
 impl State for part2::State2<'_> {
@@ -51,69 +56,14 @@ impl State for part2::State2<'_> {
     fn choices(&self, max_time: N) -> Vec<Self> {
         self.choices(max_time)
     }
-}
-
-#[derive(Copy, Clone)]
-pub struct SillySet<'a> {
-    bits: u64,
-    keyer: &'a Keyer,
-}
-
-impl<'a> SillySet<'a> {
-    pub fn new(keyer: &'a Keyer) -> Self {
-        Self { bits: 0, keyer }
-    }
-
-    pub fn contains(&self, key: &str) -> bool {
-        let k = self.keyer.get(key);
-        self.bits & k != 0
-    }
-
-    pub fn len(&self) -> usize {
-        self.bits.count_ones() as usize
-    }
-
-    pub fn insert(&mut self, key: &str) {
-        let k = self.keyer.get(key);
-        self.bits |= k;
-    }
-
-    pub fn iter(&self) -> impl Iterator<Item = &'static str> + '_ {
-        self.keyer
-            .inner
-            .iter()
-            .filter(|(_, k)| self.bits & *k != 0)
-            .map(|(x, _)| *x)
-    }
-}
 
-impl PartialEq for SillySet<'_> {
-    fn eq(&self, other: &Self) -> bool {
-        debug_assert!(std::ptr::eq(self.keyer, other.keyer));
-        self.bits == other.bits
+    fn opened_valves(&self) -> Vec<&'static str> {
+        self.opened_valves()
     }
 }
 
-impl PartialOrd for SillySet<'_> {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.bits == other.bits {
-            Some(Ordering::Equal)
-        } else if self.bits & other.bits == other.bits {
-            // self is a strict superset of other
-            Some(Ordering::Greater)
-        } else if self.bits & other.bits == self.bits {
-            // self is a strict subset of other
-            Some(Ordering::Less)
-        } else {
-            None
-        }
-    }
-}
-
-fn silly_comparison(sp: N, op: N, sv: &SillySet, ov: &SillySet) -> Option<Ordering> {
-    debug_assert!(std::ptr::eq(sv, ov));
-
-    match (sv.partial_cmp(&ov)?, sp.cmp(&op)) {
+fn silly_comparison(sp: N, op: N, sv: &BitSet, ov: &BitSet) -> Option<Ordering> {
+    match (sv.partial_cmp(ov)?, sp.cmp(&op)) {
         (Ordering::Less, Ordering::Less)
         | (Ordering::Less, Ordering::Equal)
         | (Ordering::Equal, Ordering::Less) => Some(Ordering::Less),
@@ -1,14 +1,15 @@
-use super::{silly_comparison, SillySet};
+use super::silly_comparison;
 use crate::graph::Graph;
 use crate::{Node, N};
 use std::cmp::Ordering;
+use util::bitset::BitSet;
 
 #[derive(Copy, Clone, PartialEq)]
 pub struct State1<'a> {
     graph: &'a Graph,
     pub location: &'static str,
     pub time_elapsed: N,
-    opened_valves: SillySet<'a>,
+    pub(super) opened_valves: BitSet,
     pub pressure_released: N,
 }
 
@@ -18,13 +19,29 @@ impl<'a> State1<'a> {
             graph,
             location,
             time_elapsed: 0,
-            opened_valves: SillySet::new(&graph.keyer),
+            opened_valves: BitSet::new(),
             pressure_released: 0,
         }
     }
 
+    fn is_open(&self, name: &str) -> bool {
+        self.opened_valves.contains(self.graph.keyer.index(name))
+    }
+
+    fn open(&mut self, name: &str) {
+        self.opened_valves.insert(self.graph.keyer.index(name));
+    }
+
+    pub fn opened_valves(&self) -> Vec<&'static str> {
+        self.opened_valves
+            .iter()
+            .map(|i| *self.graph.keyer.key(i))
+            .collect()
+    }
+
     fn release_pressure(&mut self, time: N) {
-        for name in self.opened_valves.iter() {
+        for i in self.opened_valves.iter() {
+            let name = *self.graph.keyer.key(i);
             self.pressure_released += self.graph.flow(name) * time;
         }
     }
@@ -50,7 +67,7 @@ impl<'a> State1<'a> {
 
     pub fn open_valve(&mut self) {
         self.elapse_time(1);
-        self.opened_valves.insert(self.location);
+        self.open(self.location);
     }
 
     pub fn travel_to(&mut self, destination: &'static str) {
@@ -69,7 +86,7 @@ impl<'a> State1<'a> {
 
         let mut selves = vec![];
 
-        if !self.opened_valves.contains(&self.location) && self.current_node().flow > 0 {
+        if !self.is_open(self.location) && self.current_node().flow > 0 {
             selves.push(self.with(Self::open_valve));
         }
 
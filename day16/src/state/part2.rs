@@ -2,7 +2,8 @@ use crate::graph::Graph;
 use crate::{Node, N};
 use std::cmp::Ordering;
 
-use super::{silly_comparison, SillySet};
+use super::silly_comparison;
+use util::bitset::BitSet;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Status {
@@ -28,7 +29,7 @@ pub struct State2<'a> {
     pub human: Status,
     pub elephant: Status,
     pub time_elapsed: N,
-    opened_valves: SillySet<'a>,
+    pub(super) opened_valves: BitSet,
     pub pressure_released: N,
 }
 
@@ -46,11 +47,26 @@ impl<'a> State2<'a> {
             human: Status::At(location),
             elephant: Status::At(location),
             time_elapsed: 0,
-            opened_valves: SillySet::new(&graph.keyer),
+            opened_valves: BitSet::new(),
             pressure_released: 0,
         }
     }
 
+    fn is_open(&self, name: &str) -> bool {
+        self.opened_valves.contains(self.graph.keyer.index(name))
+    }
+
+    fn open(&mut self, name: &str) {
+        self.opened_valves.insert(self.graph.keyer.index(name));
+    }
+
+    pub fn opened_valves(&self) -> Vec<&'static str> {
+        self.opened_valves
+            .iter()
+            .map(|i| *self.graph.keyer.key(i))
+            .collect()
+    }
+
     fn human_location(&self) -> Option<&'static str> {
         match self.human {
             Status::At(x) => Some(x),
@@ -74,7 +90,8 @@ impl<'a> State2<'a> {
     }
 
     fn apply_update(&mut self, human_action: Action, elephant_action: Action) {
-        for name in self.opened_valves.iter() {
+        for i in self.opened_valves.iter() {
+            let name = *self.graph.keyer.key(i);
             self.pressure_released += self.graph.flow(name);
         }
 
@@ -82,7 +99,7 @@ impl<'a> State2<'a> {
             Action::Idle => (),
             Action::Open => {
                 let location = self.human_location().unwrap();
-                self.opened_valves.insert(location);
+                self.open(location);
             }
             Action::Move(dest) => {
                 let node = self.human_node().unwrap();
@@ -94,7 +111,7 @@ impl<'a> State2<'a> {
             Action::Idle => (),
             Action::Open => {
                 let location = self.elephant_location().unwrap();
-                self.opened_valves.insert(location);
+                self.open(location);
             }
             Action::Move(dest) => {
                 let node = self.elephant_node().unwrap();
@@ -114,7 +131,7 @@ impl<'a> State2<'a> {
     }
 
     fn should_open(&self, node: &Node) -> bool {
-        node.flow > 0 && !self.opened_valves.contains(node.name)
+        node.flow > 0 && !self.is_open(node.name)
     }
 
     fn get_actions_for(&self, location: Option<&'static str>, max_time: N) -> Vec<Action> {
@@ -128,7 +145,7 @@ impl<'a> State2<'a> {
             }
 
             for (name, dist) in &node.adjacencies {
-                if self.graph.map[name].is_leaf() && self.opened_valves.contains(name) {
+                if self.graph.map[name].is_leaf() && self.is_open(name) {
                     continue;
                 }
 
@@ -1,224 +1,436 @@
 #![cfg_attr(test, feature(test))]
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::fmt::{self, Display};
 
-use util::*;
+use util::record::{Frame, Palette, Recorder};
+use util::sim::{hash_of, Simulation};
 
 type N = i64;
-type P = Pos<N>;
 
 type In = Vec<bool>;
 type Out = N;
 
-#[derive(Clone, Hash, PartialEq, Eq)]
-pub struct Rock {
-    points: Vec<P>,
+// One bit per column, the leftmost column in bit 0, so chambers can be up to 32 wide.
+type Row = u32;
+
+const ROCKS: &str = include_str!("../rocks.txt");
+
+// Rows from the bottom up; every shape has at least one row and no empty rows on the outside.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Shape {
+    rows: Vec<Row>,
+    width: u32,
 }
 
-impl Rock {
-    pub fn new<const LEN: usize>(points: [(N, N); LEN]) -> Rock {
-        let points = points.map(|(x, y)| P::new(x, y)).into();
-        Rock { points }
-    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShapeError {
+    Empty { shape: usize },
+    TooWide { shape: usize, width: usize },
+    BadChar { line: usize, found: char },
+}
 
-    pub fn shift(&mut self, dx: N, dy: N) {
-        for p in &mut self.points {
-            *p += (dx, dy);
+impl Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty { shape } => write!(f, "shape {shape} has no '#' in it"),
+            Self::TooWide { shape, width } => write!(f, "shape {shape} is {width} wide"),
+            Self::BadChar { line, found } => write!(f, "line {line}: {found:?} isn't '#' or '.'"),
         }
     }
+}
 
-    pub fn shifted(&self, dx: N, dy: N) -> Self {
-        let mut this = self.clone();
-        this.shift(dx, dy);
-        this
+impl std::error::Error for ShapeError {}
+
+// Pictures as in the puzzle text, top row first, separated by blank lines.
+pub fn parse_shapes(s: &str) -> Result<Vec<Shape>, ShapeError> {
+    let mut shapes = Vec::new();
+    let mut picture: Vec<Row> = Vec::new();
+    let mut width = 0;
+    let lines = s.lines().map(str::trim_end).chain([""]);
+    for (line, text) in lines.enumerate() {
+        if text.is_empty() {
+            if !picture.is_empty() || width > 0 {
+                let shape = shapes.len();
+                let rows: Vec<Row> = picture.drain(..).rev().collect();
+                let first = rows.iter().position(|&r| r != 0);
+                let last = rows.iter().rposition(|&r| r != 0);
+                let (Some(first), Some(last)) = (first, last) else {
+                    return Err(ShapeError::Empty { shape });
+                };
+                let left = rows.iter().map(|r| r.trailing_zeros()).min().unwrap();
+                let rows: Vec<Row> = rows[first..=last].iter().map(|r| r >> left).collect();
+                let width = rows
+                    .iter()
+                    .map(|r| Row::BITS - r.leading_zeros())
+                    .max()
+                    .unwrap();
+                shapes.push(Shape { rows, width });
+            }
+            width = 0;
+            continue;
+        }
+        if text.len() > Row::BITS as usize {
+            return Err(ShapeError::TooWide {
+                shape: shapes.len(),
+                width: text.len(),
+            });
+        }
+        let mut row = 0;
+        for (x, c) in text.chars().enumerate() {
+            match c {
+                '#' => row |= 1 << x,
+                '.' => {}
+                found => {
+                    return Err(ShapeError::BadChar {
+                        line: line + 1,
+                        found,
+                    })
+                }
+            }
+        }
+        width = width.max(text.len());
+        picture.push(row);
     }
+    Ok(shapes)
+}
 
-    pub fn bottom(&self) -> N {
-        self.points.iter().map(|p| p.y).min().unwrap()
-    }
+pub fn rocks() -> Vec<Shape> {
+    parse_shapes(ROCKS).unwrap_or_else(|e| panic!("rocks.txt: {e}"))
+}
 
-    pub fn top(&self) -> N {
-        self.points.iter().map(|p| p.y).max().unwrap()
-    }
+// Rocks appear `spawn_x` from the left wall and `spawn_gap` empty rows above the tower.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rules {
+    pub width: u32,
+    pub spawn_x: u32,
+    pub spawn_gap: usize,
+}
 
-    pub fn left(&self) -> N {
-        self.points.iter().map(|p| p.x).min().unwrap()
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            width: 7,
+            spawn_x: 2,
+            spawn_gap: 3,
+        }
     }
+}
 
-    pub fn right(&self) -> N {
-        self.points.iter().map(|p| p.x).max().unwrap()
-    }
+fn parse(s: &'static str) -> In {
+    s.trim().chars().map(|c| c == '>').collect()
+}
 
-    pub fn collides_with(&self, world: &World) -> bool {
-        self.points.iter().any(|p| world.contains(p))
-    }
+fn palette() -> Palette {
+    Palette::new(&[
+        [16, 16, 32],
+        [128, 128, 128],
+        [90, 160, 220],
+        [230, 120, 60],
+    ])
+}
 
-    pub fn add_to(self, world: &mut World) {
-        for p in self.points {
-            world.insert(p);
-        }
-    }
+// A rock at rest (or in flight): which shape, and where its bottom-left corner is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Placed {
+    shape: usize,
+    x: u32,
+    y: usize,
 }
 
-fn rocks() -> [Rock; 5] {
-    [
-        Rock::new([(0, 0), (1, 0), (2, 0), (3, 0)]),
-        Rock::new([(0, 1), (1, 0), (1, 1), (1, 2), (2, 1)]),
-        Rock::new([(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)]),
-        Rock::new([(0, 0), (0, 1), (0, 2), (0, 3)]),
-        Rock::new([(0, 0), (0, 1), (1, 0), (1, 1)]),
-    ]
+// Rows of the tower top kept in the cycle key; anything deeper is assumed buried for good.
+const PROFILE: usize = 64;
+
+// Which rock and which jet come next, plus what the top of the tower looks like.
+type Key = (usize, usize, Vec<Row>);
+
+pub struct Chamber<'a> {
+    rows: Vec<Row>,
+    shapes: &'a [Shape],
+    rules: Rules,
+    gases: &'a [bool],
+    gas_index: usize,
+    rock_number: N,
+    limit: N,
+    last_rock: Option<Placed>,
+    // Where each key was last seen, as (rock number, height); `None` once skipping is off or done.
+    seen: Option<HashMap<Key, (N, N)>>,
+    skipped_height: N,
+    rec: Recorder,
 }
 
-type World = HashSet<P>;
+impl<'a> Chamber<'a> {
+    pub fn new(gases: &'a [bool], shapes: &'a [Shape], rules: Rules, limit: N) -> Self {
+        assert!(!gases.is_empty() && !shapes.is_empty());
+        assert!(rules.width <= Row::BITS, "chamber is wider than a row");
+        for (i, shape) in shapes.iter().enumerate() {
+            assert!(
+                rules.spawn_x + shape.width <= rules.width,
+                "shape {i} doesn't fit"
+            );
+        }
+        Self {
+            rows: Vec::new(),
+            shapes,
+            rules,
+            gases,
+            gas_index: 0,
+            rock_number: 0,
+            limit,
+            last_rock: None,
+            seen: None,
+            skipped_height: 0,
+            rec: Recorder::new(palette()),
+        }
+    }
 
-fn parse(s: &'static str) -> In {
-    s.trim().chars().map(|c| c == '>').collect()
-}
+    pub fn skipping_cycles(mut self) -> Self {
+        self.seen = Some(HashMap::new());
+        self
+    }
+
+    pub fn height(&self) -> N {
+        self.rows.len() as N + self.skipped_height
+    }
 
-pub fn print_world(world: &World, rock: &Rock, top: N) {
-    for y in (0..top).rev() {
-        print!("|");
-        for x in 0..7 {
-            let pt = P { x, y };
-            let c = if world.contains(&pt) {
-                '#'
-            } else if rock.points.iter().any(|p| *p == pt) {
-                '@'
-            } else {
-                '.'
+    fn row(&self, y: usize) -> Row {
+        self.rows.get(y).copied().unwrap_or(0)
+    }
+
+    fn fits(&self, shape: &Shape, x: u32, y: usize) -> bool {
+        shape
+            .rows
+            .iter()
+            .enumerate()
+            .all(|(i, &r)| self.row(y + i) & (r << x) == 0)
+    }
+
+    fn is_rock(&self, rock: Option<Placed>, x: u32, y: usize) -> bool {
+        rock.is_some_and(|rock| {
+            let shape = &self.shapes[rock.shape];
+            let Some(i) = y.checked_sub(rock.y) else {
+                return false;
             };
-            print!("{c}")
-        }
-        print!("|");
-        println!();
+            x >= rock.x
+                && shape
+                    .rows
+                    .get(i)
+                    .is_some_and(|r| r >> (x - rock.x) & 1 == 1)
+        })
     }
-    println!("+-------+");
-    println!();
-}
 
-fn fall(mut gas: impl Iterator<Item = bool>, rock: &mut Rock, world: &World) {
-    loop {
-        assert!(rock.right() <= 6);
-        assert!(rock.left() >= 0);
+    fn top_rows(&self, depth: usize) -> Vec<Row> {
+        self.rows.iter().rev().take(depth).copied().collect()
+    }
 
-        let right = gas.next().unwrap();
-        if right {
-            if rock.right() < 6 {
-                let shifted = rock.shifted(1, 0);
-                if !shifted.collides_with(&world) {
-                    *rock = shifted;
+    fn fall(&mut self, shape: usize) -> Placed {
+        let rock = &self.shapes[shape];
+        let mut x = self.rules.spawn_x;
+        let mut y = self.rows.len() + self.rules.spawn_gap;
+        loop {
+            let right = self.gases[self.gas_index];
+            self.gas_index = (self.gas_index + 1) % self.gases.len();
+            if right {
+                if x + rock.width < self.rules.width && self.fits(rock, x + 1, y) {
+                    x += 1;
                 }
+            } else if x > 0 && self.fits(rock, x - 1, y) {
+                x -= 1;
             }
-        } else {
-            if rock.left() > 0 {
-                let shifted = rock.shifted(-1, 0);
-                if !shifted.collides_with(&world) {
-                    *rock = shifted;
-                }
+
+            if y == 0 || !self.fits(rock, x, y - 1) {
+                return Placed { shape, x, y };
             }
+            y -= 1;
         }
+    }
 
-        if rock.bottom() <= 0 {
-            break;
+    fn settle(&mut self, placed: Placed) {
+        let shape = &self.shapes[placed.shape];
+        let top = placed.y + shape.rows.len();
+        if self.rows.len() < top {
+            self.rows.resize(top, 0);
         }
+        for (i, &r) in shape.rows.iter().enumerate() {
+            self.rows[placed.y + i] |= r << placed.x;
+        }
+    }
 
-        let shifted = rock.shifted(0, -1);
-        if shifted.collides_with(&world) {
-            break;
-        } else {
-            *rock = shifted;
+    // The first time the next rock, jet and tower top repeat, jump over as many whole cycles as fit.
+    fn skip_cycles(&mut self, shape: usize) {
+        if self.seen.is_none() || self.rows.len() < PROFILE {
+            return;
         }
+        let key = (shape, self.gas_index, self.top_rows(PROFILE));
+        let now = (self.rock_number, self.height());
+        let seen = self.seen.as_mut().unwrap();
+        let Some(&(rocks, height)) = seen.get(&key) else {
+            seen.insert(key, now);
+            return;
+        };
+        let period = self.rock_number - rocks;
+        let cycles = (self.limit - self.rock_number) / period;
+        self.rock_number += cycles * period;
+        self.skipped_height += cycles * (now.1 - height);
+        self.seen = None;
     }
-}
 
-fn take_snapshot(world: &World, bottom: N, top: N) -> Vec<[bool; 7]> {
-    let mut rows = Vec::with_capacity((top - bottom) as usize);
-    for y in bottom..=top {
-        let row = std::array::from_fn(|i| {
-            let x = i as N;
-            world.contains(&Pos { x, y })
-        });
-        rows.push(row);
+    // The top `VIEW` rows, scrolling with the tower.
+    fn draw(&self, rock: Placed) -> Frame {
+        const VIEW: N = 48;
+        let width = self.rules.width as N;
+        let top = (self.rows.len() + self.rules.spawn_gap) as N;
+        let bottom = (top - VIEW).max(0);
+        let mut frame = Frame::new(0, 0, width + 1, VIEW);
+        for y in 0..VIEW {
+            frame.set(0, y, 1);
+            frame.set(width + 1, y, 1);
+        }
+        if bottom == 0 {
+            for x in 0..=width + 1 {
+                frame.set(x, top, 1);
+            }
+        }
+        for y in bottom..top {
+            for x in 0..self.rules.width {
+                let color = if self.is_rock(Some(rock), x, y as usize) {
+                    3
+                } else if self.row(y as usize) >> x & 1 == 1 {
+                    2
+                } else {
+                    continue;
+                };
+                frame.set(x as N + 1, top - 1 - y, color);
+            }
+        }
+        frame
     }
-    rows
 }
 
-fn part1(n: &In) -> Out {
-    let mut world = World::new();
+impl Simulation for Chamber<'_> {
+    fn step(&mut self) {
+        let shape = self.rock_number as usize % self.shapes.len();
+        self.skip_cycles(shape);
+        if self.is_done() {
+            return;
+        }
 
-    let mut gas = std::iter::repeat(n).flatten().copied();
+        let placed = self.fall(shape);
+        self.settle(placed);
+        if self.rec.is_enabled() {
+            let frame = self.draw(placed);
+            self.rec.frame(|| frame);
+        }
+        self.last_rock = Some(placed);
+        self.rock_number += 1;
+    }
 
-    let mut spawn_location = 3;
+    fn is_done(&self) -> bool {
+        self.rock_number >= self.limit
+    }
 
-    for mut rock in std::iter::repeat(rocks()).flatten().take(2022) {
-        rock.shift(2, spawn_location);
-        fall(&mut gas, &mut rock, &world);
-        spawn_location = spawn_location.max(rock.top() + 4);
-        rock.add_to(&mut world);
+    fn render(&self) -> String {
+        let top = self.rows.len();
+        let bottom = top.saturating_sub(40);
+        let mut s = String::new();
+        for y in (bottom..top).rev() {
+            s.push('|');
+            for x in 0..self.rules.width {
+                let c = if self.is_rock(self.last_rock, x, y) {
+                    '@'
+                } else if self.row(y) >> x & 1 == 1 {
+                    '#'
+                } else {
+                    '.'
+                };
+                s.push(c);
+            }
+            s.push_str("|\n");
+        }
+        if bottom == 0 {
+            s += &format!("+{}+\n", "-".repeat(self.rules.width as usize));
+        }
+        s += &format!("rock {} height {}", self.rock_number, self.height());
+        s
     }
 
-    spawn_location - 3
+    fn fingerprint(&self) -> u64 {
+        let shape = self.rock_number as usize % self.shapes.len();
+        hash_of(&(shape, self.gas_index, self.top_rows(50)))
+    }
 }
 
-fn part2(gases: &Vec<bool>) -> Out {
-    let mut world = World::new();
-
-    let rocks = rocks();
-
-    let mut spawn_location = 3;
-
-    let mut gas_index = 0;
-
-    let mut recurrences = HashMap::<_, N>::new();
-    let mut snapshots = HashMap::<_, Vec<[bool; 7]>>::new();
-    let mut snapshot_times = HashMap::new();
-
-    let mut result_offset = 0;
-
-    let mut rock_number: N = -1;
-    while rock_number < 1000000000000 {
-        rock_number += 1;
-
-        let rock_index = rock_number as usize % rocks.len();
-        let mut rock = rocks[rock_index].clone();
-        rock.shift(2, spawn_location);
+// The height of the tower once `count` rocks have come to rest.
+pub fn tower_height(gases: &[bool], shapes: &[Shape], rules: Rules, count: N) -> N {
+    let mut chamber = Chamber::new(gases, shapes, rules, count).skipping_cycles();
+    util::sim::run(&mut chamber);
+    let height = chamber.height();
+    chamber.rec.finish().unwrap();
+    height
+}
 
-        let key = (rock_index, gas_index);
-        let n = recurrences.entry(key).or_default();
-        *n += 1;
-        if *n >= 2 {
-            let snapshot = take_snapshot(&world, spawn_location - 500, spawn_location);
+fn part1(gases: &In) -> Out {
+    tower_height(gases, &rocks(), Rules::default(), 2022)
+}
 
-            let snapshot_value = (rock_number, spawn_location);
-            if snapshots.get(&key) == Some(&snapshot) {
-                let (new_rock_num, new_spawn_loc) = snapshot_value;
-                let (old_rock_num, old_spawn_loc) = snapshot_times[&key];
-                let rock_delta = new_rock_num - old_rock_num;
-                let spawn_delta = new_spawn_loc - old_spawn_loc;
+fn part2(gases: &In) -> Out {
+    tower_height(gases, &rocks(), Rules::default(), 1000000000000)
+}
 
-                while rock_number + rock_delta < 1000000000000 {
-                    rock_number += rock_delta;
-                    result_offset += spawn_delta;
-                }
-            } else {
-                snapshots.insert(key, snapshot);
-                snapshot_times.insert(key, snapshot_value);
-            }
-        }
+util::register!(parse, part1, part2, @alt);
 
-        let gas_iter = std::iter::from_fn(|| {
-            let v = gases[gas_index];
-            gas_index = (gas_index + 1) % gases.len();
-            Some(v)
-        });
-        fall(gas_iter, &mut rock, &world);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_shapes() {
+        let rocks = rocks();
+        assert_eq!(rocks.len(), 5);
+        // rows come out bottom first, shifted against the left edge
+        assert_eq!(rocks[2].rows, [0b111, 0b100, 0b100]);
+        assert_eq!(rocks[2].width, 3);
+        let shapes = parse_shapes("..#\n.##\n").unwrap();
+        assert_eq!(shapes[0].rows, [0b11, 0b10]);
+        assert_eq!(shapes[0].width, 2);
+    }
 
-        spawn_location = spawn_location.max(rock.top() + 4);
-        rock.add_to(&mut world);
+    #[test]
+    fn rejects_bad_shapes() {
+        assert_eq!(
+            parse_shapes("#\n\n...\n..\n").err(),
+            Some(ShapeError::Empty { shape: 1 })
+        );
+        assert_eq!(
+            parse_shapes("##\n#x\n").err(),
+            Some(ShapeError::BadChar {
+                line: 2,
+                found: 'x'
+            })
+        );
+        let wide = "#".repeat(Row::BITS as usize + 1);
+        assert_eq!(
+            parse_shapes(&format!("#\n\n{wide}\n")).err(),
+            Some(ShapeError::TooWide {
+                shape: 1,
+                width: wide.len()
+            })
+        );
     }
 
-    spawn_location + result_offset - 4
+    #[test]
+    fn stacks_in_a_custom_chamber() {
+        let rules = Rules {
+            width: 4,
+            spawn_x: 0,
+            spawn_gap: 3,
+        };
+        // a bar as wide as the chamber can't be pushed, so each adds a row
+        let bar = parse_shapes("####").unwrap();
+        assert_eq!(tower_height(&[true, false], &bar, rules, 1000), 1000);
+        // upright dominoes all blown right stand on each other in the right column
+        let domino = parse_shapes("#\n#").unwrap();
+        let rules = Rules { width: 2, ..rules };
+        assert_eq!(tower_height(&[true], &domino, rules, 1000), 2000);
+    }
 }
-
-util::register!(parse, part1, part2, @alt);
@@ -1,109 +1,325 @@
 #![cfg_attr(test, feature(test))]
 
-#[derive(Debug, Copy, Clone)]
-enum Rps {
-    Rock,
-    Paper,
-    Scissors,
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Outcome {
+    Win,
+    Lose,
+    Draw,
 }
 
-impl Rps {
-    fn score(self) -> u32 {
-        self as u32 + 1
-    }
+type Move = usize;
+
+// Moves sit in a circle and each beats the moves an odd number of places before it, which
+// with an odd count makes every move beat exactly half the others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Game {
+    moves: Vec<&'static str>,
+    scoring: Scoring,
+}
 
-    fn play(self, other: Self) -> u32 {
-        use Rps::*;
-        match (self, other) {
-            (Rock, Scissors) | (Paper, Rock) | (Scissors, Paper) => 6,
-            (Scissors, Rock) | (Rock, Paper) | (Paper, Scissors) => 0,
-            _ => 3,
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Scoring {
+    win: u32,
+    draw: u32,
+    lose: u32,
+    moves: Vec<u32>,
+}
+
+impl Scoring {
+    // The puzzle's: 6, 3 or 0 for the outcome, plus 1 for the first move, 2 for the next...
+    fn standard(moves: usize) -> Self {
+        Self {
+            win: 6,
+            draw: 3,
+            lose: 0,
+            moves: (1..=moves as u32).collect(),
         }
     }
 
-    fn round(self, other: Self) -> u32 {
-        self.play(other) + self.score()
+    fn value(&self, outcome: Outcome) -> u32 {
+        match outcome {
+            Outcome::Win => self.win,
+            Outcome::Lose => self.lose,
+            Outcome::Draw => self.draw,
+        }
     }
+}
 
-    fn to_outcome(self) -> Outcome {
-        match self {
-            Self::Rock => Outcome::Lose,
-            Self::Paper => Outcome::Draw,
-            Self::Scissors => Outcome::Win,
+impl Game {
+    fn new(moves: &[&'static str], scoring: Scoring) -> Self {
+        assert!(
+            moves.len() % 2 == 1,
+            "a fair cyclic game needs an odd number of moves"
+        );
+        assert_eq!(moves.len(), scoring.moves.len());
+        Self {
+            moves: moves.into(),
+            scoring,
         }
     }
 
-    fn weakness(&self) -> Self {
-        use Rps::*;
-        match self {
-            Rock => Paper,
-            Paper => Scissors,
-            Scissors => Rock,
+    fn rock_paper_scissors() -> Self {
+        Self::new(&["rock", "paper", "scissors"], Scoring::standard(3))
+    }
+
+    // Other games for other guides; neither part needs them.
+    #[allow(dead_code)]
+    fn rock_paper_scissors_spock_lizard() -> Self {
+        let moves = ["rock", "paper", "scissors", "spock", "lizard"];
+        Self::new(&moves, Scoring::standard(5))
+    }
+
+    fn outcome(&self, mine: Move, theirs: Move) -> Outcome {
+        match (mine + self.moves.len() - theirs) % self.moves.len() {
+            0 => Outcome::Draw,
+            d if d % 2 == 1 => Outcome::Win,
+            _ => Outcome::Lose,
         }
     }
 
-    fn strength(&self) -> Self {
-        use Rps::*;
-        match self {
-            Paper => Rock,
-            Scissors => Paper,
-            Rock => Scissors,
+    // The move that gets `desired` against `theirs`.
+    fn respond(&self, theirs: Move, desired: Outcome) -> Move {
+        let n = self.moves.len();
+        match desired {
+            Outcome::Draw => theirs,
+            Outcome::Win => (theirs + 1) % n,
+            Outcome::Lose => (theirs + n - 1) % n,
         }
     }
+
+    fn score(&self, mine: Move, theirs: Move) -> u32 {
+        self.scoring.value(self.outcome(mine, theirs)) + self.scoring.moves[mine]
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
-enum Outcome {
-    Win,
-    Lose,
-    Draw,
+// What a letter in the second column tells us to do.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Meaning {
+    Play(Move),
+    Get(Outcome),
+}
+
+// How to read a strategy guide: their letters are always moves, ours depend on the guide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Guide {
+    theirs: HashMap<char, Move>,
+    ours: HashMap<char, Meaning>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum GuideError {
+    UnknownLetter {
+        column: usize,
+        letter: char,
+    },
+    // a column naming moves needs one letter per move of the game
+    LetterCount {
+        column: usize,
+        letters: usize,
+        moves: usize,
+    },
+    DuplicateLetter {
+        column: usize,
+        letter: char,
+    },
 }
 
-impl Outcome {
-    pub fn value(self) -> u32 {
+impl Display for GuideError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Win => 6,
-            Self::Lose => 0,
-            Self::Draw => 3,
+            Self::UnknownLetter { column, letter } => {
+                write!(f, "{letter:?} means nothing in column {column}")
+            }
+            Self::LetterCount {
+                column,
+                letters,
+                moves,
+            } => {
+                write!(
+                    f,
+                    "{letters} letters in column {column} for a game of {moves} moves"
+                )
+            }
+            Self::DuplicateLetter { column, letter } => {
+                write!(f, "{letter:?} means two things in column {column}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GuideError {}
+
+// Each letter with its meaning, as long as no letter turns up twice.
+fn letters<T>(
+    column: usize,
+    meanings: impl IntoIterator<Item = (char, T)>,
+) -> Result<HashMap<char, T>, GuideError> {
+    let mut letters = HashMap::new();
+    for (letter, meaning) in meanings {
+        if letters.insert(letter, meaning).is_some() {
+            return Err(GuideError::DuplicateLetter { column, letter });
         }
     }
+    Ok(letters)
+}
+
+// One letter for each of the game's moves, in order.
+fn move_letters(game: &Game, column: usize, s: &str) -> Result<HashMap<char, Move>, GuideError> {
+    let moves = game.moves.len();
+    let count = s.chars().count();
+    if count != moves {
+        return Err(GuideError::LetterCount {
+            column,
+            letters: count,
+            moves,
+        });
+    }
+    letters(column, s.chars().zip(0..))
+}
+
+impl Guide {
+    // Letters in order name the game's moves in order, in both columns.
+    fn moves(game: &Game, theirs: &str, ours: &str) -> Result<Self, GuideError> {
+        let ours = move_letters(game, 2, ours)?;
+        Ok(Self {
+            theirs: move_letters(game, 1, theirs)?,
+            ours: ours
+                .into_iter()
+                .map(|(c, m)| (c, Meaning::Play(m)))
+                .collect(),
+        })
+    }
+
+    // Their letters name moves; ours say how the round should end.
+    fn outcomes(game: &Game, theirs: &str, ours: &[(char, Outcome)]) -> Result<Self, GuideError> {
+        Ok(Self {
+            theirs: move_letters(game, 1, theirs)?,
+            ours: letters(2, ours.iter().map(|&(c, o)| (c, Meaning::Get(o))))?,
+        })
+    }
+
+    fn round(&self, game: &Game, (theirs, ours): In) -> Result<u32, GuideError> {
+        let theirs = *self.theirs.get(&theirs).ok_or(GuideError::UnknownLetter {
+            column: 1,
+            letter: theirs,
+        })?;
+        let mine = match self.ours.get(&ours) {
+            Some(&Meaning::Play(m)) => m,
+            Some(&Meaning::Get(o)) => game.respond(theirs, o),
+            None => {
+                return Err(GuideError::UnknownLetter {
+                    column: 2,
+                    letter: ours,
+                })
+            }
+        };
+        Ok(game.score(mine, theirs))
+    }
+
+    fn total(&self, game: &Game, rounds: &[In]) -> Result<u32, GuideError> {
+        rounds.iter().map(|&r| self.round(game, r)).sum()
+    }
 }
 
-type In = (Rps, Rps);
+type In = (char, char);
 type Out = u32;
 
 fn parse(s: &'static str) -> In {
-    let you = match s.chars().nth(0).unwrap() {
-        'A' => Rps::Rock,
-        'B' => Rps::Paper,
-        'C' => Rps::Scissors,
-        _ => panic!(),
-    };
-    let opponent = match s.chars().nth(2).unwrap() {
-        'X' => Rps::Rock,
-        'Y' => Rps::Paper,
-        'Z' => Rps::Scissors,
-        _ => panic!(),
-    };
-    (you, opponent)
+    let mut letters = s.split_whitespace().map(|w| w.chars().next().unwrap());
+    (letters.next().unwrap(), letters.next().unwrap())
 }
 
 fn part1(n: &[In]) -> Out {
-    n.iter().map(|(x, y)| y.round(*x)).sum::<u32>()
+    let game = Game::rock_paper_scissors();
+    let guide = Guide::moves(&game, "ABC", "XYZ").unwrap();
+    guide.total(&game, n).unwrap()
 }
 
 fn part2(n: &[In]) -> Out {
-    let mut s = 0;
-    for &(x, y) in n {
-        let z = y.to_outcome();
-        let w = match z {
-            Outcome::Draw => x,
-            Outcome::Win => x.weakness(),
-            Outcome::Lose => x.strength(),
-        };
-        s += z.value() + w.score();
-    }
-    s
+    let game = Game::rock_paper_scissors();
+    let ours = [
+        ('X', Outcome::Lose),
+        ('Y', Outcome::Draw),
+        ('Z', Outcome::Win),
+    ];
+    let guide = Guide::outcomes(&game, "ABC", &ours).unwrap();
+    guide.total(&game, n).unwrap()
 }
 
 util::register!(parse, part1, part2);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUNDS: [In; 3] = [('A', 'Y'), ('B', 'X'), ('C', 'Z')];
+
+    #[test]
+    fn responses_get_what_was_asked() {
+        let games = [
+            Game::rock_paper_scissors(),
+            Game::rock_paper_scissors_spock_lizard(),
+        ];
+        for game in games {
+            let n = game.moves.len();
+            for theirs in 0..n {
+                for desired in [Outcome::Win, Outcome::Lose, Outcome::Draw] {
+                    let mine = game.respond(theirs, desired);
+                    assert_eq!(game.outcome(mine, theirs), desired);
+                }
+                let wins = (0..n).filter(|&m| game.outcome(m, theirs) == Outcome::Win);
+                assert_eq!(wins.count(), n / 2);
+            }
+        }
+    }
+
+    #[test]
+    fn scores_the_example() {
+        let game = Game::rock_paper_scissors();
+        let moves = Guide::moves(&game, "ABC", "XYZ").unwrap();
+        assert_eq!(moves.total(&game, &ROUNDS), Ok(15));
+        let ours = [
+            ('X', Outcome::Lose),
+            ('Y', Outcome::Draw),
+            ('Z', Outcome::Win),
+        ];
+        let outcomes = Guide::outcomes(&game, "ABC", &ours).unwrap();
+        assert_eq!(outcomes.total(&game, &ROUNDS), Ok(12));
+        let err = GuideError::UnknownLetter {
+            column: 2,
+            letter: 'W',
+        };
+        assert_eq!(moves.total(&game, &[('A', 'W')]), Err(err));
+    }
+
+    #[test]
+    fn rejects_bad_guides() {
+        let game = Game::rock_paper_scissors();
+        let err = GuideError::LetterCount {
+            column: 2,
+            letters: 2,
+            moves: 3,
+        };
+        assert_eq!(Guide::moves(&game, "ABC", "XY"), Err(err));
+        let err = GuideError::LetterCount {
+            column: 1,
+            letters: 5,
+            moves: 3,
+        };
+        assert_eq!(Guide::moves(&game, "ABCDE", "XYZ"), Err(err));
+        let err = GuideError::DuplicateLetter {
+            column: 1,
+            letter: 'A',
+        };
+        assert_eq!(Guide::moves(&game, "ABA", "XYZ"), Err(err));
+        let ours = [('X', Outcome::Lose), ('X', Outcome::Win)];
+        let err = GuideError::DuplicateLetter {
+            column: 2,
+            letter: 'X',
+        };
+        assert_eq!(Guide::outcomes(&game, "ABC", &ours), Err(err));
+    }
+}
@@ -11,82 +11,115 @@ fn parse(s: &'static str) -> In {
     p(s)
 }
 
-#[derive(Copy, Clone, Debug)]
-struct Num {
-    value: N,
-    index: usize,
+// The numbers in their current order, chopped into blocks of about √n so that
+// finding, removing and inserting an entry each cost O(√n).
+struct Ring {
+    blocks: Vec<Vec<usize>>,
+    block_of: Vec<usize>,
+    block_size: usize,
 }
 
-impl Num {
-    pub fn new((index, value): (usize, N)) -> Self {
-        Self { value, index }
+impl Ring {
+    fn new(len: usize) -> Self {
+        let block_size = ((len as f64).sqrt() as usize).max(16);
+        let mut ring = Self {
+            blocks: vec![(0..len).collect()],
+            block_of: vec![0; len],
+            block_size,
+        };
+        ring.rebuild();
+        ring
     }
-}
 
-fn prepare(n: &[N]) -> (Vec<Num>, Vec<usize>) {
-    let nums = n.iter().copied().enumerate().map(Num::new).collect();
-    let locations = (0..n.len()).collect();
-    (nums, locations)
+    fn rebuild(&mut self) {
+        let ids = Vec::from_iter(self.blocks.drain(..).flatten());
+        self.blocks = Vec::from_iter(ids.chunks(self.block_size).map(|c| c.to_vec()));
+        for (b, block) in self.blocks.iter().enumerate() {
+            for &id in block {
+                self.block_of[id] = b;
+            }
+        }
+    }
+
+    fn position(&self, id: usize) -> usize {
+        let b = self.block_of[id];
+        let before = self.blocks[..b].iter().map(Vec::len).sum::<usize>();
+        before + self.blocks[b].iter().position(|&x| x == id).unwrap()
+    }
+
+    fn remove(&mut self, id: usize) {
+        let block = &mut self.blocks[self.block_of[id]];
+        let k = block.iter().position(|&x| x == id).unwrap();
+        block.remove(k);
+    }
+
+    // Puts `id` at position `pos`, counting from 0 with `id` itself already removed.
+    fn insert(&mut self, pos: usize, id: usize) {
+        let mut pos = pos;
+        let mut b = 0;
+        while b + 1 < self.blocks.len() && pos > self.blocks[b].len() {
+            pos -= self.blocks[b].len();
+            b += 1;
+        }
+        self.blocks[b].insert(pos, id);
+        self.block_of[id] = b;
+
+        if self.blocks[b].len() > 2 * self.block_size {
+            self.rebuild();
+        }
+    }
+
+    fn order(&self) -> impl Iterator<Item = usize> + '_ {
+        self.blocks.iter().flatten().copied()
+    }
 }
 
-fn mix(nums: &mut [Num], locations: &mut [usize]) {
-    let nl = nums.len() as N;
+// Each number in turn, by its original position, moves along by its value.
+fn mix(values: &[N], ring: &mut Ring) {
+    let nl = values.len() as N;
+    // a lone number has nowhere to go
+    if nl < 2 {
+        return;
+    }
 
-    for ii in 0..nums.len() {
-        let i = locations[ii];
-        let v = &mut nums[i];
-        assert_eq!(ii, v.index);
+    for (id, &v) in values.iter().enumerate() {
+        let i = ring.position(id);
 
-        let mut j = i as N + v.value;
+        let mut j = i as N + v;
         j %= nl - 1;
         if j <= 0 {
             j += nl - 1;
         }
-        let j = j as usize;
 
-        if j > i {
-            nums[i..=j].rotate_left(1);
-            for k in i..j {
-                let kk = nums[k].index;
-                locations[kk] -= 1;
-            }
-            locations[ii] = j;
-        } else if j < i {
-            nums[j..=i].rotate_right(1);
-            for k in (j + 1)..=i {
-                let kk = nums[k].index;
-                locations[kk] += 1;
-            }
-            locations[ii] = j;
-        }
+        ring.remove(id);
+        ring.insert(j as usize, id);
     }
 }
 
-fn part1(n: &[In]) -> Out {
-    let (mut nums, mut locations) = prepare(n);
-    mix(&mut nums, &mut locations);
-    result(&nums)
-}
-
-fn part2(n: &[In]) -> Out {
-    let (mut nums, mut locations) = prepare(n);
+fn decrypt(n: &[N], key: N, rounds: usize) -> N {
+    let values = Vec::from_iter(n.iter().map(|v| v * key));
+    let mut ring = Ring::new(values.len());
 
-    const KEY: N = 811589153;
-    for v in &mut nums {
-        v.value *= KEY;
+    for _ in 0..rounds {
+        mix(&values, &mut ring);
     }
 
-    for _ in 0..10 {
-        mix(&mut nums, &mut locations);
-    }
+    let mixed = Vec::from_iter(ring.order().map(|id| values[id]));
+    result(&mixed)
+}
 
-    result(&nums)
+fn part1(n: &[In]) -> Out {
+    decrypt(n, 1, 1)
 }
 
-fn result(n: &[Num]) -> Out {
+fn part2(n: &[In]) -> Out {
+    decrypt(n, 811589153, 10)
+}
+
+fn result(n: &[N]) -> Out {
     std::iter::repeat(n)
         .flatten()
-        .map(|v| v.value)
+        .copied()
         .skip_while(|v| *v != 0)
         .step_by(1000)
         .skip(1)
@@ -95,3 +128,65 @@ fn result(n: &[Num]) -> Out {
 }
 
 util::register!(parse, part1, part2);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Moves one number at a time around a plain list.
+    fn naive_mix(values: &[N], order: &mut Vec<usize>) {
+        let len = values.len() as N;
+        for (id, &v) in values.iter().enumerate() {
+            let i = order.iter().position(|&x| x == id).unwrap();
+            order.remove(i);
+            let j = (i as N + v).rem_euclid(len - 1) as usize;
+            order.insert(j, id);
+        }
+    }
+
+    // Where everything is relative to the first number, since a ring has no start.
+    fn from_first(order: impl Iterator<Item = usize>) -> Vec<usize> {
+        let mut order = Vec::from_iter(order);
+        let start = order.iter().position(|&id| id == 0).unwrap();
+        order.rotate_left(start);
+        order
+    }
+
+    #[test]
+    fn matches_naive_mixing() {
+        // enough numbers for several blocks, and for blocks to overflow and be rebuilt
+        let mut seed = 12345u64;
+        let mut values = Vec::from_iter((0..300).map(|_| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as N % 2001 - 1000
+        }));
+        values[123] = 0;
+
+        for (key, rounds) in [(1, 1), (811589153, 10)] {
+            let keyed = Vec::from_iter(values.iter().map(|v| v * key));
+            let mut ring = Ring::new(keyed.len());
+            let mut order = Vec::from_iter(0..keyed.len());
+            for round in 0..rounds {
+                mix(&keyed, &mut ring);
+                naive_mix(&keyed, &mut order);
+                assert_eq!(
+                    from_first(ring.order()),
+                    from_first(order.iter().copied()),
+                    "key {key}, round {round}"
+                );
+            }
+            assert!(ring.blocks.len() > 1);
+
+            let mixed = Vec::from_iter(order.iter().map(|&id| keyed[id]));
+            assert_eq!(decrypt(&values, key, rounds), result(&mixed));
+        }
+    }
+
+    #[test]
+    fn a_lone_number_stays_put() {
+        assert_eq!(decrypt(&[0], 1, 1), 0);
+        assert_eq!(decrypt(&[0], 811589153, 10), 0);
+    }
+}
@@ -1,7 +1,12 @@
 #![cfg_attr(test, feature(test))]
 
 use std::collections::HashMap;
+use std::fmt::{self, Display};
 
+use num_rational::BigRational;
+use num_traits::Zero;
+
+use util::dot::{Dot, DotWriter, Highlight, Kind};
 use util::*;
 
 type Name = &'static str;
@@ -14,7 +19,7 @@ enum Expr {
     Op(Oper, Name, Name),
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 enum Oper {
     Add,
     Mul,
@@ -22,18 +27,187 @@ enum Oper {
     Div,
 }
 
+impl Oper {
+    fn apply(self, lhs: N, rhs: N) -> N {
+        match self {
+            Oper::Add => lhs + rhs,
+            Oper::Mul => lhs * rhs,
+            Oper::Sub => lhs - rhs,
+            Oper::Div => lhs / rhs,
+        }
+    }
+
+    fn symbol(self) -> char {
+        match self {
+            Oper::Add => '+',
+            Oper::Mul => '*',
+            Oper::Sub => '-',
+            Oper::Div => '/',
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 struct Def {
     name: Name,
     val: Expr,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+enum GraphError {
+    Undefined { name: Name, used_by: Name },
+    Duplicate(Name),
+    Cycle(Vec<Name>),
+}
+
+impl Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Undefined { name, used_by } => {
+                write!(f, "{used_by} uses {name}, which is never defined")
+            }
+            Self::Duplicate(name) => write!(f, "{name} is defined more than once"),
+            Self::Cycle(names) => write!(f, "cycle: {}", names.join(" -> ")),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Mark {
+    Visiting,
+    Done,
+}
+
+struct Monkeys<'a> {
+    defs: &'a [Def],
+    index: HashMap<Name, usize>,
+    // every name after the ones it depends on
+    order: Vec<Name>,
+    memo: HashMap<Name, N>,
+}
+
+impl<'a> Monkeys<'a> {
+    fn new(defs: &'a [Def]) -> Result<Self, GraphError> {
+        let mut index = HashMap::new();
+        for (i, def) in defs.iter().enumerate() {
+            if index.insert(def.name, i).is_some() {
+                return Err(GraphError::Duplicate(def.name));
+            }
+        }
+
+        let mut monkeys = Self {
+            defs,
+            index,
+            order: Vec::with_capacity(defs.len()),
+            memo: HashMap::new(),
+        };
+
+        let mut marks = HashMap::new();
+        let mut path = Vec::new();
+        for def in defs {
+            monkeys.visit(def.name, &mut marks, &mut path)?;
+        }
+
+        Ok(monkeys)
+    }
+
+    fn visit(
+        &mut self,
+        name: Name,
+        marks: &mut HashMap<Name, Mark>,
+        path: &mut Vec<Name>,
+    ) -> Result<(), GraphError> {
+        match marks.get(name) {
+            Some(Mark::Done) => return Ok(()),
+            Some(Mark::Visiting) => {
+                let start = path.iter().position(|&n| n == name).unwrap();
+                let mut cycle = path[start..].to_vec();
+                cycle.push(name);
+                return Err(GraphError::Cycle(cycle));
+            }
+            None => (),
+        }
+
+        marks.insert(name, Mark::Visiting);
+        path.push(name);
+
+        if let Expr::Op(_, lhs, rhs) = self.defs[self.index[name]].val {
+            for dep in [lhs, rhs] {
+                if !self.index.contains_key(dep) {
+                    return Err(GraphError::Undefined {
+                        name: dep,
+                        used_by: name,
+                    });
+                }
+                self.visit(dep, marks, path)?;
+            }
+        }
+
+        path.pop();
+        marks.insert(name, Mark::Done);
+        self.order.push(name);
+        Ok(())
+    }
+
+    fn get(&self, name: Name) -> Option<&Def> {
+        self.index.get(name).map(|&i| &self.defs[i])
+    }
+
+    // Evaluates `name` and whatever it depends on, remembering every value along the way.
+    fn value(&mut self, name: Name) -> Option<N> {
+        if let Some(&v) = self.memo.get(name) {
+            return Some(v);
+        }
+        let v = match self.get(name)?.val {
+            Expr::Literal(v) => v,
+            Expr::Op(op, lhs, rhs) => op.apply(self.value(lhs)?, self.value(rhs)?),
+        };
+        self.memo.insert(name, v);
+        Some(v)
+    }
+
+    // The chain of definitions from `from` down to `to`, inclusive.
+    fn chain(&self, from: Name, to: Name) -> Option<Vec<Name>> {
+        if from == to {
+            return Some(vec![to]);
+        }
+        let Expr::Op(_, lhs, rhs) = self.get(from)?.val else {
+            return None;
+        };
+        let mut chain = self.chain(lhs, to).or_else(|| self.chain(rhs, to))?;
+        chain.push(from);
+        Some(chain)
+    }
+}
+
+impl Dot for Monkeys<'_> {
+    const KIND: Kind = Kind::Digraph;
+
+    fn write_dot(&self, w: &mut DotWriter) {
+        for def in self.defs {
+            match def.val {
+                Expr::Literal(v) => {
+                    w.node(def.name).set("label", format!("{}\n{v}", def.name));
+                }
+                Expr::Op(op, lhs, rhs) => {
+                    let sym = op.symbol();
+                    w.node(def.name)
+                        .set("label", format!("{}\n{sym}", def.name))
+                        .set("shape", "box");
+                    w.edge(def.name, lhs).set("label", "l");
+                    w.edge(def.name, rhs).set("label", "r");
+                }
+            }
+        }
+    }
+}
+
 type In = Def;
 type Out = N;
 
 fn parse(s: &'static str) -> In {
     let toks = s.split_whitespace().collect::<Vec<_>>();
-    let name = &toks[0][..4];
+    let name = toks[0].trim_end_matches(':');
     let val = if toks.len() == 2 {
         Expr::Literal(p(toks[1]))
     } else {
@@ -52,151 +226,489 @@ fn parse(s: &'static str) -> In {
 }
 
 fn part1(n: &[In]) -> Out {
-    let mut vals = HashMap::<Name, N>::new();
+    let mut monkeys = Monkeys::new(n).unwrap_or_else(|e| panic!("{e}"));
 
-    for def in n {
-        if let Expr::Literal(v) = def.val {
-            vals.insert(def.name, v);
-        }
+    // fill in the memo bottom-up so nothing recurses deeply
+    for name in monkeys.order.clone() {
+        monkeys.value(name);
     }
 
-    while vals.len() < n.len() {
-        for def in n {
-            if let Expr::Op(op, lhs, rhs) = def.val {
-                if let (Some(lhv), Some(rhv)) = (vals.get(lhs), vals.get(rhs)) {
-                    let result = match op {
-                        Oper::Add => lhv + rhv,
-                        Oper::Mul => lhv * rhv,
-                        Oper::Sub => lhv - rhv,
-                        Oper::Div => lhv / rhv,
-                    };
+    util::dot::export(&monkeys, &Highlight::default()).unwrap();
 
-                    vals.insert(def.name, result);
-                }
-            }
-        }
-    }
+    monkeys.value("root").unwrap()
+}
+
+type Q = BigRational;
 
-    vals["root"]
+fn q(v: N) -> Q {
+    Q::from_integer(v.into())
 }
 
-#[derive(Debug, Clone)]
-enum Mv {
-    Simple(N),
-    Human(Vec<(Oper, N)>),
+// A side of the equation, with everything not involving the unknown already folded to a constant.
+#[derive(Debug, Clone, PartialEq)]
+enum Sym {
+    Num(Q),
+    Var(Name),
+    Op(Oper, Box<Sym>, Box<Sym>),
 }
 
-impl std::ops::Add for Mv {
-    type Output = Self;
+#[derive(Debug, Clone, PartialEq)]
+enum SolveError {
+    Undefined(Name),
+    NotAnOperation(Name),
+    DivisionByZero(Name),
+    NonLinear,
+    NoSolution,
+    AnyValue,
+}
 
-    fn add(self, rhs: Self) -> Self::Output {
-        match (self, rhs) {
-            (Mv::Simple(a), Mv::Simple(b)) => Mv::Simple(a + b),
-            (Mv::Simple(s), Mv::Human(mut h)) | (Mv::Human(mut h), Mv::Simple(s)) => {
-                h.push((Oper::Add, s));
-                Mv::Human(h)
-            }
-            _ => panic!("oh no"),
+impl Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Undefined(name) => write!(f, "{name} is never defined"),
+            Self::NotAnOperation(name) => write!(f, "{name} is a number, not an equation"),
+            Self::DivisionByZero(name) => write!(f, "{name} divides by zero"),
+            Self::NonLinear => write!(f, "the unknown can't be isolated"),
+            Self::NoSolution => write!(f, "no value satisfies the equation"),
+            Self::AnyValue => write!(f, "every value satisfies the equation"),
         }
     }
 }
 
-impl std::ops::Sub for Mv {
-    type Output = Self;
+// `a * x + b`
+struct Linear {
+    a: Q,
+    b: Q,
+}
+
+impl Sym {
+    fn constant(&self) -> Option<&Q> {
+        match self {
+            Sym::Num(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn linear(&self) -> Option<Linear> {
+        Some(match self {
+            Sym::Num(v) => Linear {
+                a: q(0),
+                b: v.clone(),
+            },
+            Sym::Var(_) => Linear { a: q(1), b: q(0) },
+            Sym::Op(op, lhs, rhs) => {
+                let (l, r) = (lhs.linear()?, rhs.linear()?);
+                match op {
+                    Oper::Add => Linear {
+                        a: l.a + r.a,
+                        b: l.b + r.b,
+                    },
+                    Oper::Sub => Linear {
+                        a: l.a - r.a,
+                        b: l.b - r.b,
+                    },
+                    Oper::Mul if l.a.is_zero() => Linear {
+                        a: r.a * &l.b,
+                        b: r.b * l.b,
+                    },
+                    Oper::Mul if r.a.is_zero() => Linear {
+                        a: l.a * &r.b,
+                        b: l.b * r.b,
+                    },
+                    Oper::Div if r.a.is_zero() && !r.b.is_zero() => Linear {
+                        a: l.a / &r.b,
+                        b: l.b / r.b,
+                    },
+                    _ => return None,
+                }
+            }
+        })
+    }
+
+    fn var(&self) -> Option<Name> {
+        match self {
+            Sym::Num(_) => None,
+            Sym::Var(name) => Some(name),
+            Sym::Op(_, lhs, rhs) => lhs.var().or_else(|| rhs.var()),
+        }
+    }
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        match (self, rhs) {
-            (Mv::Simple(a), Mv::Simple(b)) => Mv::Simple(a - b),
-            (Mv::Human(mut h), Mv::Simple(s)) => {
-                h.push((Oper::Sub, s));
-                Mv::Human(h)
+    // Linear parts come out as `a * x + b`; anything else keeps its shape.
+    fn simplify(self) -> Sym {
+        if let (Some(Linear { a, b }), Some(var)) = (self.linear(), self.var()) {
+            let mut sym = Sym::Var(var);
+            if a != q(1) {
+                sym = Sym::Op(Oper::Mul, Box::new(Sym::Num(a)), Box::new(sym));
+            }
+            if !b.is_zero() {
+                sym = Sym::Op(Oper::Add, Box::new(sym), Box::new(Sym::Num(b)));
             }
-            (Mv::Simple(s), Mv::Human(mut h)) => {
-                h.push((Oper::Mul, -1));
-                h.push((Oper::Add, s));
-                Mv::Human(h)
+            return sym;
+        }
+        match self {
+            Sym::Op(op, lhs, rhs) => {
+                Sym::Op(op, Box::new(lhs.simplify()), Box::new(rhs.simplify()))
             }
-            _ => panic!("oh no"),
+            sym => sym,
         }
     }
 }
 
-impl std::ops::Mul for Mv {
-    type Output = Self;
+impl Display for Sym {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sym::Num(v) if v.is_integer() => write!(f, "{v}"),
+            Sym::Num(v) => write!(f, "({v})"),
+            Sym::Var(name) => write!(f, "{name}"),
+            Sym::Op(op, lhs, rhs) => write!(f, "({lhs} {} {rhs})", op.symbol()),
+        }
+    }
+}
 
-    fn mul(self, rhs: Self) -> Self::Output {
-        match (self, rhs) {
-            (Mv::Simple(a), Mv::Simple(b)) => Mv::Simple(a * b),
-            (Mv::Simple(s), Mv::Human(mut h)) | (Mv::Human(mut h), Mv::Simple(s)) => {
-                h.push((Oper::Mul, s));
-                Mv::Human(h)
-            }
-            _ => panic!("oh no"),
+impl Monkeys<'_> {
+    fn symbolic(&self, name: Name, var: Name) -> Result<Sym, SolveError> {
+        if name == var {
+            return Ok(Sym::Var(var));
         }
+        let def = self.get(name).ok_or(SolveError::Undefined(name))?;
+        let (op, lhs, rhs) = match def.val {
+            Expr::Literal(v) => return Ok(Sym::Num(q(v))),
+            Expr::Op(op, lhs, rhs) => (op, self.symbolic(lhs, var)?, self.symbolic(rhs, var)?),
+        };
+        let (Some(l), Some(r)) = (lhs.constant(), rhs.constant()) else {
+            return Ok(Sym::Op(op, Box::new(lhs), Box::new(rhs)));
+        };
+        Ok(Sym::Num(match op {
+            Oper::Add => l + r,
+            Oper::Sub => l - r,
+            Oper::Mul => l * r,
+            Oper::Div if r.is_zero() => return Err(SolveError::DivisionByZero(name)),
+            Oper::Div => l / r,
+        }))
+    }
+
+    // The two sides of `target`, which are taken to be equal.
+    fn equation(&self, target: Name, var: Name) -> Result<(Sym, Sym), SolveError> {
+        let def = self.get(target).ok_or(SolveError::Undefined(target))?;
+        let Expr::Op(_, lhs, rhs) = def.val else {
+            return Err(SolveError::NotAnOperation(target));
+        };
+        Ok((self.symbolic(lhs, var)?, self.symbolic(rhs, var)?))
+    }
+
+    // The value of `var` that makes both sides of `target` equal, whatever `target`'s operation.
+    fn solve_for(&self, target: Name, var: Name) -> Result<Q, SolveError> {
+        let (lhs, rhs) = self.equation(target, var)?;
+        let (lhs, rhs) = (lhs.simplify(), rhs.simplify());
+        eprintln!("{lhs} = {rhs}");
+        solve(&lhs, &rhs)
     }
 }
 
-impl std::ops::Div for Mv {
-    type Output = Self;
+// Solves `sym = target` by undoing one operation at a time on the side holding the unknown.
+fn isolate(sym: &Sym, target: Q) -> Result<Q, SolveError> {
+    if let Some(Linear { a, b }) = sym.linear() {
+        return match (a.is_zero(), target == b) {
+            (false, _) => Ok((target - b) / a),
+            (true, true) => Err(SolveError::AnyValue),
+            (true, false) => Err(SolveError::NoSolution),
+        };
+    }
 
-    fn div(self, rhs: Self) -> Self::Output {
-        match (self, rhs) {
-            (Mv::Simple(a), Mv::Simple(b)) => Mv::Simple(a / b),
-            (Mv::Human(mut h), Mv::Simple(s)) => {
-                h.push((Oper::Div, s));
-                Mv::Human(h)
+    let Sym::Op(op, lhs, rhs) = sym else {
+        unreachable!()
+    };
+    match (op, lhs.constant(), rhs.constant()) {
+        (Oper::Add, Some(k), None) => isolate(rhs, target - k),
+        (Oper::Add, None, Some(k)) => isolate(lhs, target - k),
+        (Oper::Sub, Some(k), None) => isolate(rhs, k - target),
+        (Oper::Sub, None, Some(k)) => isolate(lhs, target + k),
+        (Oper::Mul, Some(k), None) | (Oper::Mul, None, Some(k)) => {
+            match (k.is_zero(), target.is_zero()) {
+                (false, _) => isolate(if lhs.constant().is_some() { rhs } else { lhs }, target / k),
+                (true, true) => Err(SolveError::AnyValue),
+                (true, false) => Err(SolveError::NoSolution),
             }
-            _ => panic!("oh no"),
+        }
+        (Oper::Div, None, Some(k)) => isolate(lhs, target * k),
+        (Oper::Div, Some(_), None) if target.is_zero() => Err(SolveError::NoSolution),
+        (Oper::Div, Some(k), None) => isolate(rhs, k / target),
+        _ => Err(SolveError::NonLinear),
+    }
+}
+
+fn solve(lhs: &Sym, rhs: &Sym) -> Result<Q, SolveError> {
+    match (lhs.constant(), rhs.constant()) {
+        (Some(k), _) => isolate(rhs, k.clone()),
+        (_, Some(k)) => isolate(lhs, k.clone()),
+        _ => {
+            // unknowns on both sides: fine as long as the difference is linear
+            let diff = Sym::Op(Oper::Sub, Box::new(lhs.clone()), Box::new(rhs.clone()));
+            isolate(&diff, q(0))
         }
     }
 }
 
 fn part2(n: &[In]) -> Out {
-    let mut vals = HashMap::<Name, Mv>::new();
+    let (root, humn) = ("root", "humn");
 
-    for def in n {
-        if let Expr::Literal(v) = def.val {
-            vals.insert(def.name, Mv::Simple(v));
-        }
+    let monkeys = Monkeys::new(n).unwrap_or_else(|e| panic!("{e}"));
+
+    if util::dot::is_enabled() {
+        let mut chain = monkeys.chain(root, humn).unwrap_or_default();
+        chain.reverse();
+        util::dot::export(&monkeys, &Highlight::path(chain)).unwrap();
     }
 
-    vals.insert("humn", Mv::Human(vec![]));
+    let v = monkeys
+        .solve_for(root, humn)
+        .unwrap_or_else(|e| panic!("{e}"));
+    assert!(v.is_integer(), "{humn} = {v} isn't a whole number");
+    N::try_from(v.to_integer()).unwrap()
+}
 
-    while vals.len() < n.len() {
-        for def in n {
-            let Expr::Op(op, lhs, rhs) = def.val else { continue };
-            let (Some(lhv), Some(rhv)) = (vals.get(lhs).cloned(), vals.get(rhs).cloned()) else { continue };
+util::register!(parse, part1, part2);
 
-            let result = match op {
-                Oper::Add => lhv + rhv,
-                Oper::Mul => lhv * rhv,
-                Oper::Sub => lhv - rhv,
-                Oper::Div => lhv / rhv,
-            };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            vals.insert(def.name, result);
-        }
+    fn defs(s: &'static str) -> Vec<Def> {
+        s.lines().map(str::trim).map(parse).collect()
     }
 
-    let root_expr = n.iter().find(|d| d.name == "root").unwrap();
-    let Expr::Op(_, lhs, rhs) = root_expr.val else { panic!() };
+    // `root`'s own operation doesn't matter to the solver.
+    fn solve_for(s: &'static str) -> Result<Q, SolveError> {
+        let defs = defs(s);
+        Monkeys::new(&defs).unwrap().solve_for("root", "humn")
+    }
 
-    let (human, target) = match (vals[lhs].clone(), vals[rhs].clone()) {
-        (Mv::Simple(s), Mv::Human(h)) | (Mv::Human(h), Mv::Simple(s)) => (h, s),
-        _ => panic!(),
-    };
+    #[test]
+    fn orders_dependencies_first() {
+        let defs = defs(
+            "root: a + b
+            b: c * c
+            a: 1
+            c: 3",
+        );
+        let monkeys = Monkeys::new(&defs).unwrap();
+        let at = |name| monkeys.order.iter().position(|&n| n == name).unwrap();
+        assert_eq!(monkeys.order.len(), 4);
+        assert!(at("a") < at("root") && at("b") < at("root") && at("c") < at("b"));
+    }
 
-    let mut v = target;
-    for (op, operand) in human.into_iter().rev() {
-        match op {
-            Oper::Add => v -= operand,
-            Oper::Sub => v += operand,
-            Oper::Mul => v /= operand,
-            Oper::Div => v *= operand,
-        }
+    #[test]
+    fn reports_duplicates() {
+        let defs = defs(
+            "root: a + b
+            a: 1
+            b: 2
+            a: 3",
+        );
+        let err = Monkeys::new(&defs).err().unwrap();
+        assert_eq!(err, GraphError::Duplicate("a"));
+        assert_eq!(err.to_string(), "a is defined more than once");
     }
 
-    v
-}
+    #[test]
+    fn reports_undefined_names() {
+        let defs = defs(
+            "root: a + b
+            a: 1
+            b: a * c",
+        );
+        let err = Monkeys::new(&defs).err().unwrap();
+        assert_eq!(
+            err,
+            GraphError::Undefined {
+                name: "c",
+                used_by: "b"
+            }
+        );
+        assert_eq!(err.to_string(), "b uses c, which is never defined");
+    }
 
-util::register!(parse, part1, part2);
+    #[test]
+    fn reports_cycles() {
+        let chain = defs(
+            "root: a + one
+            a: b * one
+            b: c - one
+            c: a / one
+            one: 1",
+        );
+        let err = Monkeys::new(&chain).err().unwrap();
+        assert_eq!(err, GraphError::Cycle(vec!["a", "b", "c", "a"]));
+        assert_eq!(err.to_string(), "cycle: a -> b -> c -> a");
+
+        let looped = defs("root: root + root");
+        let err = Monkeys::new(&looped).err().unwrap();
+        assert_eq!(err, GraphError::Cycle(vec!["root", "root"]));
+    }
+
+    #[test]
+    fn remembers_values() {
+        let defs = defs(
+            "root: a + b
+            a: c * two
+            b: c - two
+            c: 5
+            two: 2",
+        );
+        let mut monkeys = Monkeys::new(&defs).unwrap();
+        assert_eq!(monkeys.value("b"), Some(3));
+        assert_eq!(monkeys.memo.len(), 3);
+        assert_eq!(monkeys.value("nobody"), None);
+
+        // a remembered value is used as is, without looking at the definition again
+        monkeys.memo.insert("c", 100);
+        assert_eq!(monkeys.value("a"), Some(200));
+        assert_eq!(monkeys.value("root"), Some(203));
+        assert_eq!(monkeys.memo.len(), 5);
+    }
+
+    #[test]
+    fn inverts_with_the_constant_on_the_left() {
+        // 10 - 12 / x = 7, so 12 / x = 3
+        let v = solve_for(
+            "root: lhs + seven
+            lhs: ten - div
+            div: twelve / humn
+            seven: 7
+            ten: 10
+            twelve: 12
+            humn: 0",
+        );
+        assert_eq!(v, Ok(q(4)));
+        // 20 / (x - 1) = 4, so x - 1 = 5
+        let v = solve_for(
+            "root: lhs + four
+            lhs: twenty / sub
+            sub: humn - one
+            twenty: 20
+            four: 4
+            one: 1
+            humn: 0",
+        );
+        assert_eq!(v, Ok(q(6)));
+    }
+
+    #[test]
+    fn unknown_on_both_sides() {
+        // 3 * x - 4 = x + 8
+        let v = solve_for(
+            "root: lhs + rhs
+            lhs: triple - four
+            triple: three * humn
+            rhs: humn + eight
+            three: 3
+            four: 4
+            eight: 8
+            humn: 0",
+        );
+        assert_eq!(v, Ok(q(6)));
+    }
+
+    #[test]
+    fn reports_non_linear_equations() {
+        let v = solve_for(
+            "root: square + nine
+            square: humn * humn
+            nine: 9
+            humn: 0",
+        );
+        assert_eq!(v, Err(SolveError::NonLinear));
+        // the unknown in two divisors
+        let v = solve_for(
+            "root: lhs + rhs
+            lhs: one / humn
+            rhs: humn / one
+            one: 1
+            humn: 0",
+        );
+        assert_eq!(v, Err(SolveError::NonLinear));
+    }
+
+    #[test]
+    fn no_solution_or_any_value() {
+        let v = solve_for(
+            "root: zero + five
+            zero: humn - humn
+            five: 5
+            humn: 0",
+        );
+        assert_eq!(v, Err(SolveError::NoSolution));
+        let v = solve_for(
+            "root: lhs + rhs
+            lhs: humn + five
+            rhs: five + humn
+            five: 5
+            humn: 0",
+        );
+        assert_eq!(v, Err(SolveError::AnyValue));
+        // k / x never reaches zero
+        let v = solve_for(
+            "root: lhs + zero
+            lhs: five / humn
+            five: 5
+            zero: 0
+            humn: 0",
+        );
+        assert_eq!(v, Err(SolveError::NoSolution));
+    }
+
+    #[test]
+    fn exact_non_integer_roots() {
+        // 2 * x = 3, where truncating division would give 1
+        let v = solve_for(
+            "root: lhs + three
+            lhs: two * humn
+            two: 2
+            three: 3
+            humn: 0",
+        );
+        assert_eq!(v, Ok(Q::new(3.into(), 2.into())));
+    }
+
+    #[test]
+    fn any_target_and_variable() {
+        let defs = defs(
+            "root: a + b
+            a: x * two
+            b: 7
+            two: 2
+            x: 5
+            check: a - b",
+        );
+        let monkeys = Monkeys::new(&defs).unwrap();
+        // a = 7, whatever root's own operation is
+        assert_eq!(
+            monkeys.solve_for("root", "x"),
+            Ok(Q::new(7.into(), 2.into()))
+        );
+        assert_eq!(
+            monkeys.solve_for("check", "two"),
+            Ok(Q::new(7.into(), 5.into()))
+        );
+        assert_eq!(
+            monkeys.solve_for("b", "x"),
+            Err(SolveError::NotAnOperation("b"))
+        );
+        assert_eq!(
+            monkeys.solve_for("nobody", "x"),
+            Err(SolveError::Undefined("nobody"))
+        );
+    }
+
+    #[test]
+    fn reports_division_by_zero() {
+        let v = solve_for(
+            "root: lhs + humn
+            lhs: one / zero
+            one: 1
+            zero: 0
+            humn: 0",
+        );
+        assert_eq!(v, Err(SolveError::DivisionByZero("lhs")));
+    }
+}
@@ -1,7 +1,9 @@
 #![cfg_attr(test, feature(test))]
 
 use std::collections::HashMap;
+use std::str::FromStr;
 
+use util::svg::{Style, Svg};
 use util::*;
 
 #[derive(Copy, Clone, PartialEq)]
@@ -71,7 +73,7 @@ fn parse(s: &'static str) -> In {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 enum Direction {
     North,
     East,
@@ -106,317 +108,351 @@ impl Direction {
     }
 }
 
-fn is_tile(tile: Option<&Tile>) -> bool {
-    matches!(tile, Some(Tile::Solid | Tile::Open))
-}
-
-fn is_void(tile: Option<&Tile>) -> bool {
-    matches!(tile, Some(Tile::Void) | None)
-}
-
-fn part1(n: &In) -> Out {
-    let In {
-        board,
-        moves,
-        turns,
-    } = n;
-
-    let mut pos = P {
-        x: board[0].iter().position(|t| *t == Tile::Open).unwrap() as N,
-        y: 0,
-    };
-    let mut facing = Direction::East;
-
-    let mut foo = moves.iter().copied();
-    let mut bar = turns.iter().copied();
-    loop {
-        let Some(mv) = foo.next() else { panic!() };
-        for _ in 0..mv {
-            let tentative_pos = match facing {
-                Direction::North => {
-                    let max_y = board
-                        .iter()
-                        .rposition(|row| is_tile(row.get(pos.x)))
-                        .unwrap();
-
-                    let y = if pos.y == 0 || is_void(board[pos.y - 1].get(pos.x)) {
-                        max_y
-                    } else {
-                        pos.y - 1
-                    };
+fn drawing(board: &[Vec<Tile>], trace: &[(P, Direction)]) -> Svg {
+    let mut svg = Svg::new();
 
-                    P { x: pos.x, y }
-                }
-                Direction::South => {
-                    let min_y = board
-                        .iter()
-                        .position(|row| is_tile(row.get(pos.x)))
-                        .unwrap();
-
-                    let y = if is_void(board.get(pos.y + 1).and_then(|row| row.get(pos.x))) {
-                        min_y
-                    } else {
-                        pos.y + 1
-                    };
-
-                    P { x: pos.x, y }
-                }
-                Direction::East => {
-                    let row = &board[pos.y];
-
-                    let min_x = row.iter().position(|t| is_tile(Some(t))).unwrap();
-
-                    let x = if is_void(row.get(pos.x + 1)) {
-                        min_x
-                    } else {
-                        pos.x + 1
-                    };
-
-                    P { x, y: pos.y }
-                }
-                Direction::West => {
-                    let row = &board[pos.y];
-
-                    let max_x = row
-                        .iter()
-                        .rposition(|t| matches!(t, Tile::Open | Tile::Solid))
-                        .unwrap();
-
-                    let x = if pos.x == 0 || is_void(row.get(pos.x - 1)) {
-                        max_x
-                    } else {
-                        pos.x - 1
-                    };
-
-                    P { x, y: pos.y }
-                }
+    for (y, row) in board.iter().enumerate() {
+        let mut x = 0;
+        for run in row.chunk_by(|a, b| a == b) {
+            let color = match run[0] {
+                Tile::Open => Some("#eeeeee"),
+                Tile::Solid => Some("#555555"),
+                Tile::Void => None,
             };
-            if board[tentative_pos.y][tentative_pos.x] == Tile::Solid {
-                break;
-            } else {
-                pos = tentative_pos;
+            if let Some(color) = color {
+                let style = Style::new().fill(color);
+                svg.rect(x as f64, y as f64, run.len() as f64, 1.0, style);
             }
+            x += run.len();
         }
+    }
 
-        let Some(turn) = bar.next() else { break };
-        facing = facing.turn(turn);
+    let center = |p: P| (p.x as f64 + 0.5, p.y as f64 + 0.5);
+
+    // break the line wherever the walk wrapped around
+    for leg in trace.chunk_by(|(a, _), (b, _)| a.x.abs_diff(b.x) + a.y.abs_diff(b.y) <= 1) {
+        let points = leg.iter().map(|(p, _)| center(*p));
+        svg.polyline(points, Style::new().stroke("orange", 1.0));
     }
 
-    let row = pos.y + 1; // excuse me?
-    let column = pos.x + 1;
-    let fcng = match facing {
-        Direction::East => 0,
-        Direction::South => 1,
-        Direction::West => 2,
-        Direction::North => 3,
-    };
+    for &(pos, facing) in trace {
+        let (x, y) = center(pos);
+        let (dx, dy) = match facing {
+            Direction::North => (0.0, -0.4),
+            Direction::East => (0.4, 0.0),
+            Direction::South => (0.0, 0.4),
+            Direction::West => (-0.4, 0.0),
+        };
+        let arrow = [
+            (x + dx, y + dy),
+            (x - dx - dy, y - dy + dx),
+            (x - dx + dy, y - dy - dx),
+        ];
+        svg.polygon(arrow, Style::new().fill("red"));
+    }
 
-    1000 * row + 4 * column + fcng
+    svg
 }
 
-fn build_wraps(_: &In) -> HashMap<P, (P, &'static [Turn])> {
-    let mut wraps = HashMap::new();
+fn is_tile(tile: Option<&Tile>) -> bool {
+    matches!(tile, Some(Tile::Solid | Tile::Open))
+}
 
-    macro_rules! add_wrap {
-        ($x0:expr,$y0:expr,$x1:expr,$y1:expr,$turns0:expr, $turns1:expr) => {{
-            let p0 = P::new($x0, $y0);
-            let p1 = P::new($x1, $y1);
-            wraps.insert(p0, (p1, $turns0));
-            wraps.insert(p1, (p0, $turns1));
-        }};
-    }
+type V = [i64; 3];
 
-    let left: &[Turn] = &[Turn::Left];
-    let right: &[Turn] = &[Turn::Right];
-    let uturn: &[Turn] = &[Turn::Left; 2];
-    let noturn: &[Turn] = &[];
-
-    // hardcoded, sorry
-    if cfg!(test) {
-        // 1,3
-        for y0 in 0..4 {
-            let x0 = 8;
-            let y1 = 4;
-            let x1 = y0 + 4;
-            add_wrap!(x0, y0, x1, y1, left, right);
-        }
+fn neg(v: V) -> V {
+    v.map(|c| -c)
+}
 
-        // 1,2
-        for x0 in 8..12 {
-            let y0 = 0;
-            let x1 = x0 - 8;
-            let y1 = 4;
-            add_wrap!(x0, y0, x1, y1, uturn, uturn);
-        }
+fn dot(a: V, b: V) -> i64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
 
-        // 2,4
-        for y0 in 4..8 {
-            let x0 = 0;
-            let x1 = 15;
-            let y1 = y0 + 4;
-            add_wrap!(x0, y0, x1, y1, noturn, noturn);
-        }
+// Where a face of the net ended up once folded: its outward normal, and where
+// its +x and +y directions point on the cube.
+#[derive(Debug, Copy, Clone)]
+struct Face {
+    corner: P,
+    normal: V,
+    right: V,
+    down: V,
+}
 
-        // 3,5
-        for x0 in 4..8 {
-            let y0 = 7;
-            let x1 = 7;
-            let y1 = 15 - x0;
-            add_wrap!(x0, y0, x1, y1, right, left);
+impl Face {
+    fn axis(&self, facing: Direction) -> V {
+        match facing {
+            Direction::North => neg(self.down),
+            Direction::East => self.right,
+            Direction::South => self.down,
+            Direction::West => neg(self.right),
         }
+    }
 
-        // 2,5
-        for x0 in 0..4 {
-            let y0 = 7;
-            let x1 = 11 - x0;
-            let y1 = 11;
-            add_wrap!(x0, y0, x1, y1, uturn, uturn);
-        }
+    fn facing(&self, axis: V) -> Direction {
+        [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ]
+        .into_iter()
+        .find(|&d| self.axis(d) == axis)
+        .unwrap()
+    }
 
-        // 4,6
-        for y0 in 4..8 {
-            let x0 = 11;
-            let x1 = 19 - y0;
-            let y1 = 8;
-            add_wrap!(x0, y0, x1, y1, right, left);
-        }
-    } else {
-        for x0 in 50..100 {
-            let y0 = 0;
-            let x1 = 0;
-            let y1 = 100 + x0;
-            add_wrap!(x0, y0, x1, y1, right, left);
+    // The face across the edge in `facing`. Carrying on in that direction
+    // there now heads back into the cube.
+    fn roll(&self, facing: Direction, corner: P) -> Face {
+        let n = self.normal;
+        let (right, down) = match facing {
+            Direction::North => (self.right, n),
+            Direction::East => (neg(n), self.down),
+            Direction::South => (self.right, neg(n)),
+            Direction::West => (n, self.down),
+        };
+        Face {
+            corner,
+            normal: self.axis(facing),
+            right,
+            down,
         }
+    }
 
-        for y0 in 50..100 {
-            let x0 = 50;
-            let x1 = y0 - 50;
-            let y1 = 100;
-            add_wrap!(x0, y0, x1, y1, left, right);
-        }
+    // Cube coordinates are doubled so cell centres land on integers.
+    fn cube_point(&self, pos: P, size: N) -> V {
+        let s = size as i64;
+        let i = 2 * (pos.x - self.corner.x) as i64 + 1 - s;
+        let j = 2 * (pos.y - self.corner.y) as i64 + 1 - s;
+        [0, 1, 2].map(|k| self.normal[k] * s + self.right[k] * i + self.down[k] * j)
+    }
 
-        for x0 in 50..100 {
-            let y0 = 149;
-            let x1 = 49;
-            let y1 = 100 + x0;
-            add_wrap!(x0, y0, x1, y1, right, left);
-        }
+    fn cell_at(&self, v: V, size: N) -> P {
+        let s = size as i64;
+        let i = (dot(v, self.right) + s - 1) / 2;
+        let j = (dot(v, self.down) + s - 1) / 2;
+        P::new(self.corner.x + i as N, self.corner.y + j as N)
+    }
+}
 
-        for y0 in 100..150 {
-            let x0 = 99;
-            let x1 = 149;
-            let y1 = 149 - y0;
-            add_wrap!(x0, y0, x1, y1, uturn, uturn);
+fn fold(board: &[Vec<Tile>]) -> (N, Vec<Face>) {
+    let area = board.iter().flatten().filter(|t| is_tile(Some(t))).count();
+    let size = (1..).find(|s| 6 * s * s >= area).unwrap();
+    assert_eq!(6 * size * size, area, "board isn't six square faces");
+
+    let is_face = |p: P| p.y < board.len() && is_tile(board[p.y].get(p.x));
+
+    let start = P::new(board[0].iter().position(|t| is_tile(Some(t))).unwrap(), 0);
+    let mut faces = vec![Face {
+        corner: start,
+        normal: [0, 0, 1],
+        right: [1, 0, 0],
+        down: [0, 1, 0],
+    }];
+
+    let mut i = 0;
+    while i < faces.len() {
+        let face = faces[i];
+        let P { x, y } = face.corner;
+        let neighbours = [
+            (Direction::North, y.checked_sub(size).map(|y| P::new(x, y))),
+            (Direction::East, Some(P::new(x + size, y))),
+            (Direction::South, Some(P::new(x, y + size))),
+            (Direction::West, x.checked_sub(size).map(|x| P::new(x, y))),
+        ];
+        for (facing, corner) in neighbours {
+            let Some(corner) = corner else { continue };
+            if is_face(corner) && faces.iter().all(|f| f.corner != corner) {
+                faces.push(face.roll(facing, corner));
+            }
         }
+        i += 1;
+    }
 
-        for x0 in 100..150 {
-            let y0 = 0;
-            let x1 = x0 - 100;
-            let y1 = 199;
-            add_wrap!(x0, y0, x1, y1, noturn, noturn);
-        }
+    assert_eq!(faces.len(), 6, "net doesn't fold into a cube");
+    (size, faces)
+}
 
-        for x0 in 100..150 {
-            let y0 = 49;
-            let x1 = 99;
-            let y1 = x0 - 50;
-            add_wrap!(x0, y0, x1, y1, right, left);
-        }
+// For every edge cell and direction that walks off the net: where you end up, and facing which way.
+fn build_wraps(n: &In) -> HashMap<(P, Direction), (P, Direction)> {
+    let (size, faces) = fold(&n.board);
+
+    let mut wraps = HashMap::new();
 
-        for y0 in 0..50 {
-            let x0 = 50;
-            let x1 = 0;
-            let y1 = 149 - y0;
-            add_wrap!(x0, y0, x1, y1, uturn, uturn);
+    for face in &faces {
+        let P { x: x0, y: y0 } = face.corner;
+        let (x1, y1) = (x0 + size - 1, y0 + size - 1);
+        let edges = [
+            (
+                Direction::North,
+                Vec::from_iter((x0..=x1).map(|x| P::new(x, y0))),
+            ),
+            (
+                Direction::East,
+                Vec::from_iter((y0..=y1).map(|y| P::new(x1, y))),
+            ),
+            (
+                Direction::South,
+                Vec::from_iter((x0..=x1).map(|x| P::new(x, y1))),
+            ),
+            (
+                Direction::West,
+                Vec::from_iter((y0..=y1).map(|y| P::new(x0, y))),
+            ),
+        ];
+
+        for (facing, cells) in edges {
+            // an edge the net already joins to another face isn't walked off
+            if ahead(&n.board, cells[0], facing).is_some() {
+                continue;
+            }
+            let axis = face.axis(facing);
+            let other = faces.iter().find(|f| f.normal == axis).unwrap();
+
+            for pos in cells {
+                // half a cell out to the edge, half a cell down the other side
+                let v = face.cube_point(pos, size);
+                let v = [0, 1, 2].map(|k| v[k] + axis[k] - face.normal[k]);
+                let target = other.cell_at(v, size);
+                wraps.insert((pos, facing), (target, other.facing(neg(face.normal))));
+            }
         }
     }
 
     wraps
 }
 
-fn part2(n: &In) -> Out {
-    let wraps = build_wraps(n);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Topology {
+    // off an edge, back in at the far end of the same row or column
+    Flat,
+    Cube,
+    // off an edge of the whole board, back in at the opposite one; void is a wall
+    Torus,
+    // edges and void are walls
+    Walled,
+}
 
-    let In {
-        board,
-        moves,
-        turns,
-    } = n;
+impl FromStr for Topology {
+    type Err = String;
 
-    let mut pos = P {
-        x: board[0].iter().position(|t| *t == Tile::Open).unwrap() as N,
-        y: 0,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat" => Ok(Topology::Flat),
+            "cube" => Ok(Topology::Cube),
+            "torus" => Ok(Topology::Torus),
+            "walled" => Ok(Topology::Walled),
+            _ => Err(format!("unknown topology {s:?}")),
+        }
+    }
+}
+
+fn ahead(board: &[Vec<Tile>], pos: P, facing: Direction) -> Option<P> {
+    let p = match facing {
+        Direction::North => P::new(pos.x, pos.y.checked_sub(1)?),
+        Direction::East => P::new(pos.x + 1, pos.y),
+        Direction::South => P::new(pos.x, pos.y + 1),
+        Direction::West => P::new(pos.x.checked_sub(1)?, pos.y),
     };
-    let mut facing = Direction::East;
+    is_tile(board.get(p.y).and_then(|row| row.get(p.x))).then_some(p)
+}
 
-    let mut foo = moves.iter().copied();
-    let mut bar = turns.iter().copied();
-    loop {
-        let Some(mv) = foo.next() else { panic!() };
-        for _i in 0..mv {
-            let mut wrap_turns = None;
-
-            macro_rules! do_wrap {
-                () => {{
-                    let (wp, wt) = &wraps[&pos];
-                    wrap_turns = Some(wt);
-                    *wp
-                }};
+// Where every step from every open tile goes, walls included: a blocked step stays put.
+struct Moves(HashMap<(P, Direction), (P, Direction)>);
+
+impl Moves {
+    fn new(n: &In, topology: Topology) -> Self {
+        let board = &n.board;
+        let wraps = match topology {
+            Topology::Cube => build_wraps(n),
+            _ => HashMap::new(),
+        };
+        let height = board.len();
+        let width = board.iter().map(|row| row.len()).max().unwrap();
+
+        let step = |pos: P, facing: Direction| {
+            if let Some(p) = ahead(board, pos, facing) {
+                return Some((p, facing));
             }
-
-            let tentative_pos = match facing {
-                Direction::North => {
-                    if pos.y == 0 || is_void(board[pos.y - 1].get(pos.x)) {
-                        do_wrap!()
-                    } else {
-                        P::new(pos.x, pos.y - 1)
+            match topology {
+                Topology::Flat => {
+                    let mut p = pos;
+                    while let Some(q) = ahead(board, p, facing.left().left()) {
+                        p = q;
                     }
+                    Some((p, facing))
                 }
-
-                Direction::South => {
-                    if is_void(board.get(pos.y + 1).and_then(|row| row.get(pos.x))) {
-                        do_wrap!()
-                    } else {
-                        P::new(pos.x, pos.y + 1)
-                    }
+                Topology::Cube => Some(wraps[&(pos, facing)]),
+                Topology::Torus => {
+                    let p = match facing {
+                        Direction::North => P::new(pos.x, (pos.y + height - 1) % height),
+                        Direction::East => P::new((pos.x + 1) % width, pos.y),
+                        Direction::South => P::new(pos.x, (pos.y + 1) % height),
+                        Direction::West => P::new((pos.x + width - 1) % width, pos.y),
+                    };
+                    is_tile(board[p.y].get(p.x)).then_some((p, facing))
                 }
+                Topology::Walled => None,
+            }
+        };
 
-                Direction::East => {
-                    let row = &board[pos.y];
-                    if is_void(row.get(pos.x + 1)) {
-                        do_wrap!()
-                    } else {
-                        P::new(pos.x + 1, pos.y)
-                    }
+        let mut moves = HashMap::new();
+        for (y, row) in board.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                if *tile != Tile::Open {
+                    continue;
                 }
-                Direction::West => {
-                    let row = &board[pos.y];
-
-                    if pos.x == 0 || is_void(row.get(pos.x - 1)) {
-                        do_wrap!()
-                    } else {
-                        P::new(pos.x - 1, pos.y)
-                    }
+                let pos = P::new(x, y);
+                for facing in [
+                    Direction::North,
+                    Direction::East,
+                    Direction::South,
+                    Direction::West,
+                ] {
+                    let next = match step(pos, facing) {
+                        Some((p, f)) if board[p.y][p.x] == Tile::Open => (p, f),
+                        _ => (pos, facing),
+                    };
+                    moves.insert((pos, facing), next);
                 }
-            };
+            }
+        }
+
+        Self(moves)
+    }
+}
+
+struct Walk {
+    password: N,
+    trace: Vec<(P, Direction)>,
+}
+
+fn walk(n: &In, topology: Topology) -> Walk {
+    let moves = Moves::new(n, topology);
+
+    let mut pos = P {
+        x: n.board[0].iter().position(|t| *t == Tile::Open).unwrap() as N,
+        y: 0,
+    };
+    let mut facing = Direction::East;
+    let mut trace = vec![(pos, facing)];
 
-            if board[tentative_pos.y][tentative_pos.x] == Tile::Solid {
+    let mut turns = n.turns.iter().copied();
+    for &mv in &n.moves {
+        for _ in 0..mv {
+            let next = moves.0[&(pos, facing)];
+            if next == (pos, facing) {
                 break;
-            } else {
-                pos = tentative_pos;
-                if let Some(turns) = wrap_turns {
-                    for turn in *turns {
-                        facing = facing.turn(*turn);
-                    }
-                }
             }
+            (pos, facing) = next;
+            trace.push(next);
         }
 
-        let Some(turn) = bar.next() else { break };
+        let Some(turn) = turns.next() else { break };
         facing = facing.turn(turn);
+        trace.push((pos, facing));
     }
 
-    let row = pos.y + 1;
+    let row = pos.y + 1; // excuse me?
     let column = pos.x + 1;
     let fcng = match facing {
         Direction::East => 0,
@@ -425,7 +461,173 @@ fn part2(n: &In) -> Out {
         Direction::North => 3,
     };
 
-    1000 * row + 4 * column + fcng
+    Walk {
+        password: 1000 * row + 4 * column + fcng,
+        trace,
+    }
+}
+
+fn part1(n: &In) -> Out {
+    // `--opt topology=torus` and so on walks the board some other way
+    let topology = util::opt("topology").map_or(Topology::Flat, |t| {
+        t.parse().unwrap_or_else(|e| panic!("{e}"))
+    });
+    let walk = walk(n, topology);
+    if util::svg::is_enabled() {
+        util::svg::export(&drawing(&n.board, &walk.trace)).unwrap();
+    }
+    walk.password
+}
+
+fn part2(n: &In) -> Out {
+    let walk = walk(n, Topology::Cube);
+    if util::svg::is_enabled() {
+        util::svg::export(&drawing(&n.board, &walk.trace)).unwrap();
+    }
+    walk.password
 }
 
 util::register!(parse, part1, part2, @alt);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One face per cell, the first net below.
+    const TINY_NET: &str = "\
+.
+....
+.
+
+0R4";
+
+    // Off the right end of a row, and the top and bottom of a column.
+    const RAGGED: &str = "\
+..#.
+....
+ .. 
+
+0";
+
+    #[test]
+    fn walks_every_topology() {
+        let n = parse(TINY_NET);
+        let password = |topology| walk(&n, topology).password;
+        // four steps south from the top face go right round the cube
+        assert_eq!(password(Topology::Cube), 1005);
+        // the column is three tiles high, so the others end a step past the start
+        assert_eq!(password(Topology::Flat), 2005);
+        assert_eq!(password(Topology::Torus), 2005);
+        assert_eq!(password(Topology::Walled), 3005);
+
+        let trace = walk(&n, Topology::Cube).trace;
+        let cells = Vec::from_iter(trace.iter().map(|(p, _)| p.pair()));
+        assert_eq!(cells, [(0, 0), (0, 0), (0, 1), (0, 2), (2, 1), (0, 0)]);
+    }
+
+    #[test]
+    fn steps_off_the_edges() {
+        let n = parse(RAGGED);
+        let topologies = [Topology::Flat, Topology::Torus, Topology::Walled];
+        let moves = topologies.map(|t| Moves::new(&n, t));
+        let step = |x, y, facing| {
+            Vec::from_iter(moves.iter().map(|m| m.0[&(P::new(x, y), facing)].0.pair()))
+        };
+        use Direction::*;
+        // flat, torus, walled
+        assert_eq!(step(3, 1, East), [(0, 1), (0, 1), (3, 1)]);
+        assert_eq!(step(0, 0, North), [(0, 1), (0, 0), (0, 0)]);
+        assert_eq!(step(2, 2, East), [(1, 2), (2, 2), (2, 2)]);
+        assert_eq!(step(1, 2, South), [(1, 0), (1, 0), (1, 2)]);
+        assert_eq!(step(3, 0, North), [(3, 1), (3, 0), (3, 0)]);
+        // walls and moves within the board are the same everywhere
+        assert_eq!(step(1, 0, East), [(1, 0), (1, 0), (1, 0)]);
+        assert_eq!(step(1, 1, North), [(1, 0), (1, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn names_topologies() {
+        assert_eq!("torus".parse(), Ok(Topology::Torus));
+        assert_eq!("walled".parse(), Ok(Topology::Walled));
+        assert!("klein".parse::<Topology>().is_err());
+    }
+
+    // The eleven nets of a cube, a `#` per face.
+    const NETS: [&[&str]; 11] = [
+        &["#...", "####", "#..."],
+        &["#...", "####", ".#.."],
+        &["#...", "####", "..#."],
+        &["#...", "####", "...#"],
+        &[".#..", "####", ".#.."],
+        &[".#..", "####", "..#."],
+        &["##..", ".###", ".#.."],
+        &["##..", ".###", "..#."],
+        &["##..", ".###", "...#"],
+        &["##..", ".##.", "..##"],
+        &["###..", "..###"],
+    ];
+
+    fn blow_up(net: &[&str], size: N) -> In {
+        let mut board = Vec::new();
+        for row in net {
+            let tiles = Vec::from_iter(row.chars().flat_map(|c| {
+                let tile = if c == '#' { Tile::Open } else { Tile::Void };
+                std::iter::repeat_n(tile, size)
+            }));
+            board.extend(std::iter::repeat_n(tiles, size));
+        }
+        In {
+            board,
+            moves: vec![],
+            turns: vec![],
+        }
+    }
+
+    // The net turned a quarter clockwise.
+    fn rotate(net: &[&str]) -> Vec<String> {
+        let rows = Vec::from_iter(net.iter().map(|r| r.as_bytes()));
+        let width = rows[0].len();
+        let turned = (0..width).map(|x| {
+            let column = rows.iter().rev().map(|r| r[x] as char);
+            String::from_iter(column)
+        });
+        turned.collect()
+    }
+
+    fn back(facing: Direction) -> Direction {
+        facing.left().left()
+    }
+
+    #[test]
+    fn folds_every_net_into_reciprocal_wraps() {
+        let plain = NETS.map(|net| Vec::from_iter(net.iter().map(|r| r.to_string())));
+        let mirrored =
+            NETS.map(|net| Vec::from_iter(net.iter().map(|r| r.chars().rev().collect())));
+        for mut turned in plain.into_iter().chain(mirrored) {
+            for _ in 0..4 {
+                let rows = Vec::from_iter(turned.iter().map(String::as_str));
+                for size in [1, 2, 3, 5] {
+                    let input = blow_up(&rows, size);
+                    let (_, faces) = fold(&input.board);
+                    let mut normals = Vec::from_iter(faces.iter().map(|f| f.normal));
+                    normals.sort();
+                    normals.dedup();
+                    assert_eq!(normals.len(), 6, "{rows:?}");
+
+                    // a cube has 12 edges and the net joins 5 of them
+                    let wraps = build_wraps(&input);
+                    assert_eq!(wraps.len(), 14 * size, "{rows:?} at {size}");
+                    for (&(pos, facing), &(target, arrival)) in &wraps {
+                        assert!(is_tile(input.board[target.y].get(target.x)));
+                        assert_eq!(
+                            wraps.get(&(target, back(arrival))),
+                            Some(&(pos, back(facing))),
+                            "{rows:?} at {size}: {pos:?} {facing:?}"
+                        );
+                    }
+                }
+                turned = rotate(&rows);
+            }
+        }
+    }
+}
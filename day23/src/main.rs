@@ -2,6 +2,8 @@
 
 use std::collections::{HashMap, HashSet};
 
+use util::record::{Frame, Palette, Recorder};
+use util::sim::{hash_of, Simulation};
 use util::*;
 
 type N = i32;
@@ -14,7 +16,7 @@ fn parse(s: &'static str) -> In {
     s.bytes().map(|b| b == b'#').collect()
 }
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone)]
 enum Direction {
     North,
     South,
@@ -37,16 +39,74 @@ impl Direction {
     }
 }
 
-fn all_neighbors() -> Vec<(N, N)> {
-    let mut v = vec![];
-    for x in -1..=1 {
-        for y in -1..=1 {
-            if (x, y) != (0, 0) {
-                v.push((x, y));
+// A direction an elf may propose to step in, and the cells that have to be empty for it to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Proposal {
+    step: (N, N),
+    blockers: Vec<(N, N)>,
+}
+
+impl Proposal {
+    fn toward(d: Direction) -> Self {
+        Self {
+            step: d.primary_position(),
+            blockers: d.positions().into(),
+        }
+    }
+}
+
+// Elves with nobody in `crowd` stay put; the rest try `order`, which starts `rotation` places
+// further along each round.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rules {
+    order: Vec<Proposal>,
+    crowd: Vec<(N, N)>,
+    rotation: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        let mut crowd = vec![];
+        for x in -1..=1 {
+            for y in -1..=1 {
+                if (x, y) != (0, 0) {
+                    crowd.push((x, y));
+                }
             }
         }
+        Self {
+            order: [
+                Direction::North,
+                Direction::South,
+                Direction::West,
+                Direction::East,
+            ]
+            .map(Proposal::toward)
+            .into(),
+            crowd,
+            rotation: 1,
+        }
+    }
+}
+
+impl Rules {
+    fn order(&self, round: N) -> impl Iterator<Item = &Proposal> {
+        let start = round as usize * self.rotation % self.order.len();
+        self.order[start..].iter().chain(&self.order[..start])
+    }
+
+    // How far from an elf any rule looks or steps.
+    fn reach(&self) -> N {
+        let offsets = self
+            .order
+            .iter()
+            .flat_map(|p| p.blockers.iter().chain([&p.step]));
+        offsets
+            .chain(&self.crowd)
+            .map(|&(x, y)| x.abs().max(y.abs()))
+            .max()
+            .unwrap_or(0)
     }
-    v
 }
 
 fn gather_elves(grid: &[Vec<bool>]) -> HashSet<P> {
@@ -63,24 +123,51 @@ fn gather_elves(grid: &[Vec<bool>]) -> HashSet<P> {
     elves
 }
 
-fn gather_proposals(elves: &HashSet<P>, directions: [Direction; 4]) -> HashMap<P, Vec<P>> {
+trait Engine {
+    fn new(elves: &HashSet<P>) -> Self;
+
+    // Plays round number `round` (from 0) and tells whether anybody moved.
+    fn round(&mut self, rules: &Rules, round: N) -> bool;
+
+    fn count(&self) -> usize;
+
+    // The corners of the smallest rectangle holding every elf, kept up to date as they move.
+    fn bounds(&self) -> Option<(P, P)>;
+
+    fn contains(&self, p: P) -> bool;
+
+    // Everyone, gathered up on demand; too slow to call every round.
+    fn elves(&self) -> HashSet<P>;
+}
+
+fn bounds_of(elves: impl IntoIterator<Item = P>) -> Option<(P, P)> {
+    elves.into_iter().fold(None, |b, e| match b {
+        None => Some((e, e)),
+        Some((min, max)) => Some((
+            P::new(min.x.min(e.x), min.y.min(e.y)),
+            P::new(max.x.max(e.x), max.y.max(e.y)),
+        )),
+    })
+}
+
+// The straightforward engine: a set of positions and a map of who proposed what.
+struct Sparse {
+    elves: HashSet<P>,
+    bounds: Option<(P, P)>,
+}
+
+fn gather_proposals(elves: &HashSet<P>, rules: &Rules, round: N) -> HashMap<P, Vec<P>> {
     let mut proposals = HashMap::<P, Vec<P>>::new();
 
     for &elf in elves {
         let mut proposal = elf;
 
-        if all_neighbors()
-            .into_iter()
-            .any(|n| elves.contains(&(elf + n)))
-        {
-            'dir: for d in directions {
-                for n in d.positions() {
-                    if elves.contains(&(elf + n)) {
-                        continue 'dir;
-                    }
+        if rules.crowd.iter().any(|&n| elves.contains(&(elf + n))) {
+            for p in rules.order(round) {
+                if p.blockers.iter().all(|&n| !elves.contains(&(elf + n))) {
+                    proposal = elf + p.step;
+                    break;
                 }
-                proposal = elf + d.primary_position();
-                break;
             }
         }
 
@@ -111,52 +198,501 @@ fn perform_movement(proposals: HashMap<P, Vec<P>>, elves: &mut HashSet<P>) -> bo
     moved
 }
 
-fn part1(n: &[In]) -> Out {
-    let mut elves = gather_elves(n);
+impl Engine for Sparse {
+    fn new(elves: &HashSet<P>) -> Self {
+        Self {
+            elves: elves.clone(),
+            bounds: bounds_of(elves.iter().copied()),
+        }
+    }
 
-    let mut directions = [
-        Direction::North,
-        Direction::South,
-        Direction::West,
-        Direction::East,
-    ];
+    fn round(&mut self, rules: &Rules, round: N) -> bool {
+        let proposals = gather_proposals(&self.elves, rules, round);
+        let moved = perform_movement(proposals, &mut self.elves);
+        if moved {
+            self.bounds = bounds_of(self.elves.iter().copied());
+        }
+        moved
+    }
 
-    for _ in 0..10 {
-        let proposals = gather_proposals(&elves, directions);
-        perform_movement(proposals, &mut elves);
-        directions.rotate_left(1);
+    fn count(&self) -> usize {
+        self.elves.len()
     }
 
-    let x0 = elves.iter().map(|e| e.x).min().unwrap();
-    let y0 = elves.iter().map(|e| e.y).min().unwrap();
-    let x1 = elves.iter().map(|e| e.x).max().unwrap();
-    let y1 = elves.iter().map(|e| e.y).max().unwrap();
+    fn bounds(&self) -> Option<(P, P)> {
+        self.bounds
+    }
+
+    fn contains(&self, p: P) -> bool {
+        self.elves.contains(&p)
+    }
 
-    (x1 - x0 + 1) * (y1 - y0 + 1) - elves.len() as N
+    fn elves(&self) -> HashSet<P> {
+        self.elves.clone()
+    }
 }
 
-fn part2(n: &[In]) -> Out {
-    let mut elves = gather_elves(n);
+type Word = u128;
+const WORD: N = Word::BITS as N;
+
+// Rows of bits, `words` words to a row: column x is bit x % WORD of word x / WORD.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Plane {
+    words: usize,
+    height: usize,
+    bits: Vec<Word>,
+}
 
-    let mut directions = [
-        Direction::North,
-        Direction::South,
-        Direction::West,
-        Direction::East,
-    ];
+impl Plane {
+    fn new(words: usize, height: usize) -> Self {
+        Self {
+            words,
+            height,
+            bits: vec![0; words * height],
+        }
+    }
+
+    fn width(&self) -> N {
+        self.words as N * WORD
+    }
+
+    // Resizes to match `other`, with nothing set.
+    fn clear_like(&mut self, other: &Plane) {
+        self.words = other.words;
+        self.height = other.height;
+        self.bits.clear();
+        self.bits.resize(other.bits.len(), 0);
+    }
+
+    fn get(&self, x: N, y: usize) -> bool {
+        self.bits[y * self.words + (x / WORD) as usize] >> (x % WORD) & 1 == 1
+    }
+
+    fn set(&mut self, x: N, y: usize) {
+        self.bits[y * self.words + (x / WORD) as usize] |= 1 << (x % WORD);
+    }
 
-    let mut i = 0;
-    loop {
-        i += 1;
+    // Word i of row y, moved so each bit x holds what bit x + dx did, for |dx| < WORD.
+    fn look(&self, y: N, i: usize, dx: N) -> Word {
+        let Some(row) = usize::try_from(y).ok().filter(|&y| y < self.height) else {
+            return 0;
+        };
+        let row = &self.bits[row * self.words..][..self.words];
+        let k = dx.unsigned_abs();
+        match dx {
+            0 => row[i],
+            1.. => row[i] >> k | row.get(i + 1).map_or(0, |w| w << (WORD as u32 - k)),
+            _ => row[i] << k | i.checked_sub(1).map_or(0, |j| row[j] >> (WORD as u32 - k)),
+        }
+    }
+
+    // Sets everyone in `src` with a neighbour at (dx, dy).
+    fn or_looking(&mut self, src: &Plane, (dx, dy): (N, N)) {
+        for y in 0..self.height {
+            for i in 0..self.words {
+                self.bits[y * self.words + i] |= src.look(y as N + dy, i, dx);
+            }
+        }
+    }
 
-        let proposals = gather_proposals(&elves, directions);
-        let moved = perform_movement(proposals, &mut elves);
-        directions.rotate_left(1);
+    fn zip_with(&mut self, other: &Plane, f: impl Fn(Word, Word) -> Word) {
+        for (a, &b) in self.bits.iter_mut().zip(&other.bits) {
+            *a = f(*a, b);
+        }
+    }
 
-        if !moved {
-            break i;
+    // The smallest rectangle of (x, y) holding every set bit.
+    fn bounds(&self) -> Option<((N, N), (N, N))> {
+        let mut b: Option<((N, N), (N, N))> = None;
+        for (j, &w) in self.bits.iter().enumerate().filter(|(_, &w)| w != 0) {
+            let y = (j / self.words) as N;
+            let x = (j % self.words) as N * WORD;
+            let (x0, x1) = (
+                x + w.trailing_zeros() as N,
+                x + WORD - 1 - w.leading_zeros() as N,
+            );
+            b = Some(match b {
+                None => ((x0, y), (x1, y)),
+                Some((min, max)) => ((min.0.min(x0), min.1), (max.0.max(x1), y)),
+            });
         }
+        b
     }
 }
 
+// Layers reused from round to round, so a round allocates nothing.
+#[derive(Default)]
+struct Scratch {
+    crowded: Plane,
+    blocked: Plane,
+    undecided: Plane,
+    staying: Plane,
+    once: Plane,
+    twice: Plane,
+    // one each per proposal: who takes it, and where they'd land
+    takes: Vec<Plane>,
+    targets: Vec<Plane>,
+    steps: Vec<(N, N)>,
+}
+
+// Rows of bits with the grove's top-left corner at `origin`, so whole rows move at once.
+struct Bitboard {
+    origin: P,
+    rows: Plane,
+    count: usize,
+    bounds: Option<(P, P)>,
+    scratch: Scratch,
+}
+
+impl Bitboard {
+    fn with_margin(elves: &HashSet<P>, margin: N) -> Self {
+        let (min, max) = bounds_of(elves.iter().copied()).unwrap_or_default();
+        let origin = P::new(min.x - margin, min.y - margin);
+        let words = ((max.x + margin - origin.x) / WORD + 1) as usize;
+        let mut rows = Plane::new(words, (max.y + margin - origin.y + 1) as usize);
+        for e in elves {
+            rows.set(e.x - origin.x, (e.y - origin.y) as usize);
+        }
+        let mut board = Self {
+            origin,
+            rows,
+            count: elves.len(),
+            bounds: None,
+            scratch: Scratch::default(),
+        };
+        board.measure();
+        board
+    }
+
+    fn measure(&mut self) {
+        let at = |(x, y)| self.origin + (x, y);
+        self.bounds = self.rows.bounds().map(|(min, max)| (at(min), at(max)));
+    }
+
+    // Whether anybody is within `reach` of the edge, where this round could lose them.
+    fn needs_room(&self, reach: N) -> bool {
+        let Some((min, max)) = self.bounds else {
+            return false;
+        };
+        let (w, h) = (self.rows.width(), self.rows.height as N);
+        let from_origin = (-self.origin.x, -self.origin.y);
+        let (min, max) = (min + from_origin, max + from_origin);
+        min.x < reach || min.y < reach || max.x >= w - reach || max.y >= h - reach
+    }
+}
+
+impl Engine for Bitboard {
+    fn new(elves: &HashSet<P>) -> Self {
+        Self::with_margin(elves, 16)
+    }
+
+    fn round(&mut self, rules: &Rules, round: N) -> bool {
+        let reach = rules.reach();
+        assert!(reach < WORD, "rules look too far for a bitboard");
+        if self.needs_room(reach) {
+            let scratch = std::mem::take(&mut self.scratch);
+            *self = Self::with_margin(&self.elves(), reach + 16);
+            self.scratch = scratch;
+        }
+
+        let rows = &self.rows;
+        let s = &mut self.scratch;
+        let n = rules.order.len();
+        for plane in [
+            &mut s.crowded,
+            &mut s.blocked,
+            &mut s.undecided,
+            &mut s.staying,
+        ]
+        .into_iter()
+        .chain(&mut s.takes)
+        .chain(&mut s.targets)
+        .chain([&mut s.once, &mut s.twice])
+        {
+            plane.clear_like(rows);
+        }
+        s.takes
+            .resize_with(n, || Plane::new(rows.words, rows.height));
+        s.targets
+            .resize_with(n, || Plane::new(rows.words, rows.height));
+
+        // Who proposes what, one layer per proposal, at the proposing elf.
+        for &o in &rules.crowd {
+            s.crowded.or_looking(rows, o);
+        }
+        s.undecided.clone_from(rows);
+        s.undecided.zip_with(&s.crowded, |u, c| u & c);
+        s.steps.clear();
+        for (p, takes) in rules.order(round).zip(&mut s.takes) {
+            s.blocked.clear_like(rows);
+            for &o in &p.blockers {
+                s.blocked.or_looking(rows, o);
+            }
+            takes.clone_from(&s.undecided);
+            takes.zip_with(&s.blocked, |u, b| u & !b);
+            s.undecided.zip_with(takes, |u, t| u & !t);
+            s.steps.push(p.step);
+        }
+
+        // The same proposals at their targets, counted once and twice to find collisions.
+        s.staying.clone_from(rows);
+        for takes in &s.takes {
+            s.staying.zip_with(takes, |r, t| r & !t);
+        }
+        s.once.clone_from(&s.staying);
+        for ((takes, target), &(dx, dy)) in s.takes.iter().zip(&mut s.targets).zip(&s.steps) {
+            target.or_looking(takes, (-dx, -dy));
+            for ((once, twice), &t) in s
+                .once
+                .bits
+                .iter_mut()
+                .zip(&mut s.twice.bits)
+                .zip(&target.bits)
+            {
+                *twice |= *once & t;
+                *once |= t;
+            }
+        }
+
+        // Everyone stays put but those who land alone; the rest go back where they were.
+        let mut moved = false;
+        for ((takes, target), &step) in s.takes.iter().zip(&s.targets).zip(&s.steps) {
+            s.blocked.clear_like(rows);
+            s.blocked.or_looking(&s.twice, step);
+            let words = s
+                .staying
+                .bits
+                .iter_mut()
+                .zip(&target.bits)
+                .zip(&s.twice.bits);
+            for (((next, &t), &twice), (&takes, &blocked)) in
+                words.zip(takes.bits.iter().zip(&s.blocked.bits))
+            {
+                let arrived = t & !twice;
+                moved |= arrived != 0;
+                *next |= arrived | takes & blocked;
+            }
+        }
+        std::mem::swap(&mut self.rows, &mut s.staying);
+        if moved {
+            self.measure();
+        }
+        moved
+    }
+
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    fn bounds(&self) -> Option<(P, P)> {
+        self.bounds
+    }
+
+    fn contains(&self, p: P) -> bool {
+        let (x, y) = (p.x - self.origin.x, p.y - self.origin.y);
+        (0..self.rows.width()).contains(&x)
+            && (0..self.rows.height as N).contains(&y)
+            && self.rows.get(x, y as usize)
+    }
+
+    fn elves(&self) -> HashSet<P> {
+        let mut elves = HashSet::new();
+        for y in 0..self.rows.height {
+            for x in 0..self.rows.width() {
+                if self.rows.get(x, y) {
+                    elves.insert(self.origin + (x, y as N));
+                }
+            }
+        }
+        elves
+    }
+}
+
+// Runs two engines side by side and insists they agree after every round.
+struct Checked<A, B>(A, B);
+
+impl<A: Engine, B: Engine> Engine for Checked<A, B> {
+    fn new(elves: &HashSet<P>) -> Self {
+        Self(A::new(elves), B::new(elves))
+    }
+
+    fn round(&mut self, rules: &Rules, round: N) -> bool {
+        let moved = (self.0.round(rules, round), self.1.round(rules, round));
+        assert_eq!(moved.0, moved.1, "engines disagree on round {round}");
+        assert_eq!(
+            self.0.count(),
+            self.1.count(),
+            "engines disagree on round {round}"
+        );
+        assert_eq!(
+            self.0.bounds(),
+            self.1.bounds(),
+            "engines disagree on round {round}"
+        );
+        assert!(
+            self.0.elves() == self.1.elves(),
+            "engines disagree on round {round}"
+        );
+        moved.0
+    }
+
+    fn count(&self) -> usize {
+        self.0.count()
+    }
+
+    fn bounds(&self) -> Option<(P, P)> {
+        self.0.bounds()
+    }
+
+    fn contains(&self, p: P) -> bool {
+        self.0.contains(p)
+    }
+
+    fn elves(&self) -> HashSet<P> {
+        self.0.elves()
+    }
+}
+
+fn recorder() -> Recorder {
+    Recorder::new(Palette::new(&[[16, 16, 32], [120, 200, 90]]))
+}
+
+fn draw(elves: &HashSet<P>) -> Frame {
+    Frame::from_points(elves.iter().map(|e| (e.x as i64, e.y as i64)), 1)
+}
+
+struct Grove<E> {
+    engine: E,
+    rules: Rules,
+    round: N,
+    limit: Option<N>,
+    moved: bool,
+    rec: Recorder,
+}
+
+impl<E: Engine> Grove<E> {
+    fn new(n: &[In], rules: Rules, limit: Option<N>) -> Self {
+        let elves = gather_elves(n);
+        let mut rec = recorder();
+        rec.frame(|| draw(&elves));
+        Self {
+            engine: E::new(&elves),
+            rules,
+            round: 0,
+            limit,
+            moved: true,
+            rec,
+        }
+    }
+
+    fn bounds(&self) -> (P, P) {
+        self.engine.bounds().expect("no elves")
+    }
+}
+
+impl<E: Engine> Simulation for Grove<E> {
+    fn step(&mut self) {
+        self.moved = self.engine.round(&self.rules, self.round);
+        self.round += 1;
+        self.rec.frame(|| draw(&self.engine.elves()));
+    }
+
+    fn is_done(&self) -> bool {
+        !self.moved || Some(self.round) == self.limit
+    }
+
+    fn render(&self) -> String {
+        let (min, max) = self.bounds();
+        let mut s = String::new();
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                s.push(if self.engine.contains(P::new(x, y)) {
+                    '#'
+                } else {
+                    '.'
+                });
+            }
+            s.push('\n');
+        }
+        s += &format!("round {}", self.round);
+        s
+    }
+
+    fn fingerprint(&self) -> u64 {
+        let mut elves = Vec::from_iter(self.engine.elves());
+        elves.sort();
+        let priority = self.round as usize * self.rules.rotation % self.rules.order.len();
+        hash_of(&(elves, priority))
+    }
+}
+
+fn part1(n: &[In]) -> Out {
+    let mut grove = Grove::<Checked<Sparse, Bitboard>>::new(n, Rules::default(), Some(10));
+    util::sim::run(&mut grove);
+
+    let (min, max) = grove.bounds();
+    let empty = (max.x - min.x + 1) * (max.y - min.y + 1) - grove.engine.count() as N;
+    grove.rec.finish().unwrap();
+    empty
+}
+
+fn part2(n: &[In]) -> Out {
+    let mut grove = Grove::<Bitboard>::new(n, Rules::default(), None);
+    util::sim::run(&mut grove);
+    grove.rec.finish().unwrap();
+    grove.round
+}
+
 util::register!(parse, part1, part2);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // About half the cells of a `w` by `h` grove, picked by a little LCG.
+    fn scatter(w: usize, h: usize, mut seed: u64) -> Vec<In> {
+        let mut row = || {
+            Vec::from_iter((0..w).map(|_| {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                seed >> 63 == 1
+            }))
+        };
+        Vec::from_iter((0..h).map(|_| row()))
+    }
+
+    fn play<E: Engine>(grove: &[In], rules: Rules, rounds: N) -> Grove<E> {
+        let mut grove = Grove::<E>::new(grove, rules, Some(rounds));
+        util::sim::run(&mut grove);
+        grove
+    }
+
+    #[test]
+    fn engines_agree() {
+        // wide enough for rows of several words, spreading far enough to regrow the board
+        let grove = scatter(140, 16, 23);
+        let checked = play::<Checked<Sparse, Bitboard>>(&grove, Rules::default(), 100);
+        assert_eq!(checked.round, 100);
+        let elves = grove.iter().flatten().filter(|&&e| e).count();
+        assert_eq!(checked.engine.count(), elves);
+    }
+
+    #[test]
+    fn engines_agree_on_other_rules() {
+        let d = |x, y| Proposal {
+            step: (x, y),
+            blockers: vec![(x, y), (2 * x, 2 * y)],
+        };
+        let rules = Rules {
+            order: vec![d(1, 0), d(0, 1), d(-1, 0), d(0, -1), d(1, 1)],
+            crowd: vec![(1, 0), (-1, 0), (0, 1), (0, -1), (2, 0)],
+            rotation: 3,
+        };
+        let grove = scatter(60, 12, 5);
+        let checked = play::<Checked<Sparse, Bitboard>>(&grove, rules.clone(), 100);
+        let bitboard = play::<Bitboard>(&grove, rules, 100);
+        assert_eq!(checked.round, bitboard.round);
+        assert_eq!(checked.engine.elves(), bitboard.engine.elves());
+    }
+}
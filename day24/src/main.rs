@@ -2,6 +2,8 @@
 
 use std::collections::HashSet;
 
+use util::record::{Frame, Palette, Recorder};
+use util::sim::{hash_of, Simulation};
 use util::*;
 
 type N = i32;
@@ -76,31 +78,88 @@ fn part2(n: &[In]) -> Out {
     solution(n, true)
 }
 
-fn solution(n: &[In], part2: bool) -> Out {
-    let h = n.len() as N - 1;
-    let w = n[0].len() as N - 1;
-
-    let mut blizzards = Vec::new();
-    for (y, row) in n.iter().enumerate() {
-        for (x, tile) in row.iter().enumerate() {
-            if let Tile::Blizzard(dir) = *tile {
-                let pos = P::new(x as N, y as N);
-                blizzards.push(Blizzard { pos, dir });
+fn draw(w: N, h: N, blizzards: &[Blizzard], selves: &[Person]) -> Frame {
+    let mut frame = Frame::new(0, 0, w as i64, h as i64);
+    for x in 0..=w {
+        frame.set(x as i64, 0, 1);
+        frame.set(x as i64, h as i64, 1);
+    }
+    for y in 0..=h {
+        frame.set(0, y as i64, 1);
+        frame.set(w as i64, y as i64, 1);
+    }
+    frame.set(1, 0, 0);
+    frame.set(w as i64 - 1, h as i64, 0);
+    for b in blizzards {
+        frame.set(b.pos.x as i64, b.pos.y as i64, 2);
+    }
+    for s in selves {
+        frame.set(s.pos.x as i64, s.pos.y as i64, 3);
+    }
+    frame
+}
+
+struct Valley {
+    w: N,
+    h: N,
+    blizzards: Vec<Blizzard>,
+    selves: Vec<Person>,
+    occupied: HashSet<P>,
+    part2: bool,
+    minute: usize,
+    done: bool,
+    rec: Recorder,
+}
+
+impl Valley {
+    fn new(n: &[In], part2: bool) -> Self {
+        let h = n.len() as N - 1;
+        let w = n[0].len() as N - 1;
+
+        let mut blizzards = Vec::new();
+        for (y, row) in n.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                if let Tile::Blizzard(dir) = *tile {
+                    let pos = P::new(x as N, y as N);
+                    blizzards.push(Blizzard { pos, dir });
+                }
             }
         }
+
+        let selves = vec![Person {
+            pos: P::new(1, 0),
+            journey_leg: Leg::First,
+        }];
+
+        let palette = Palette::new(&[
+            [16, 16, 32],
+            [128, 128, 128],
+            [120, 170, 230],
+            [240, 200, 60],
+        ]);
+
+        Self {
+            w,
+            h,
+            blizzards,
+            selves,
+            occupied: HashSet::new(),
+            part2,
+            minute: 0,
+            done: false,
+            rec: Recorder::new(palette).scale(8),
+        }
     }
+}
 
-    let mut selves = vec![Person {
-        pos: P::new(1, 0),
-        journey_leg: Leg::First,
-    }];
+impl Simulation for Valley {
+    fn step(&mut self) {
+        let (w, h) = (self.w, self.h);
 
-    let mut occupied = HashSet::new();
+        self.rec.frame(|| draw(w, h, &self.blizzards, &self.selves));
 
-    let mut i = 0;
-    loop {
-        occupied.clear();
-        for b in &mut blizzards {
+        self.occupied.clear();
+        for b in &mut self.blizzards {
             b.pos += b.dir.offset();
             if b.pos.x == 0 {
                 b.pos.x = w - 1;
@@ -112,11 +171,11 @@ fn solution(n: &[In], part2: bool) -> Out {
             } else if b.pos.y == h {
                 b.pos.y = 1;
             }
-            occupied.insert(b.pos);
+            self.occupied.insert(b.pos);
         }
 
         let mut futures = HashSet::new();
-        for pr in selves {
+        for &pr in &self.selves {
             futures.insert(pr);
             for d in [Dir::North, Dir::South, Dir::East, Dir::West] {
                 let mut prd = pr;
@@ -128,18 +187,18 @@ fn solution(n: &[In], part2: bool) -> Out {
         futures.retain(|p| p.pos.x > 0 && p.pos.x < w);
         futures.retain(|p| p.pos.y > 0 || p.pos.x == 1);
         futures.retain(|p| p.pos.y < h || p.pos.x == w - 1);
-        futures.retain(|p| !occupied.contains(&p.pos));
+        futures.retain(|p| !self.occupied.contains(&p.pos));
 
-        i += 1;
+        self.minute += 1;
 
-        selves = Vec::with_capacity(futures.len());
+        self.selves = Vec::with_capacity(futures.len());
         for mut f in futures {
-            if part2 {
+            if self.part2 {
                 if f.pos == P::new(w - 1, h) {
                     match f.journey_leg {
                         Leg::First => f.journey_leg = Leg::Second,
                         Leg::Second => (),
-                        Leg::Third => return i,
+                        Leg::Third => self.done = true,
                     }
                 } else if f.pos == P::new(1, 0) {
                     match f.journey_leg {
@@ -149,12 +208,74 @@ fn solution(n: &[In], part2: bool) -> Out {
                     }
                 }
             } else if f.pos == P::new(w - 1, h) {
-                return i;
+                self.done = true;
+            }
+
+            self.selves.push(f);
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+
+    fn render(&self) -> String {
+        let mut grid = vec![vec!['.'; self.w as usize + 1]; self.h as usize + 1];
+        for (y, row) in grid.iter_mut().enumerate() {
+            for (x, c) in row.iter_mut().enumerate() {
+                let (x, y) = (x as N, y as N);
+                if x == 0 || y == 0 || x == self.w || y == self.h {
+                    *c = '#';
+                }
             }
+        }
+        grid[0][1] = '.';
+        grid[self.h as usize][self.w as usize - 1] = '.';
 
-            selves.push(f);
+        for b in &self.blizzards {
+            let c = &mut grid[b.pos.y as usize][b.pos.x as usize];
+            *c = match (*c, b.dir) {
+                ('.', Dir::North) => '^',
+                ('.', Dir::East) => '>',
+                ('.', Dir::South) => 'v',
+                ('.', Dir::West) => '<',
+                ('2'..='8', _) => (*c as u8 + 1) as char,
+                _ => '2',
+            };
         }
+        for s in &self.selves {
+            let row = usize::try_from(s.pos.y).ok().and_then(|y| grid.get_mut(y));
+            if let Some(c) = row.and_then(|r| r.get_mut(s.pos.x as usize)) {
+                *c = match s.journey_leg {
+                    Leg::First => 'E',
+                    Leg::Second => 'F',
+                    Leg::Third => 'G',
+                };
+            }
+        }
+
+        let mut s = String::new();
+        for row in grid {
+            s.extend(row);
+            s.push('\n');
+        }
+        s += &format!("minute {}, {} positions", self.minute, self.selves.len());
+        s
+    }
+
+    fn fingerprint(&self) -> u64 {
+        let mut selves = Vec::from_iter(self.selves.iter().map(|s| (s.pos, s.journey_leg as u8)));
+        selves.sort();
+        let blizzards = Vec::from_iter(self.blizzards.iter().map(|b| (b.pos, b.dir)));
+        hash_of(&(blizzards, selves))
     }
 }
 
+fn solution(n: &[In], part2: bool) -> Out {
+    let mut valley = Valley::new(n, part2);
+    util::sim::run(&mut valley);
+    valley.rec.finish().unwrap();
+    valley.minute
+}
+
 util::register!(parse, part1, part2);
@@ -1,73 +1,306 @@
 #![cfg_attr(test, feature(test))]
 
-type N = i64;
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
+use std::iter::Sum;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
 
-type In = &'static str;
+type In = Snafu;
 type Out = String;
 
-fn parse(s: &'static str) -> In {
-    s
+// The digits of a balanced base-k system, from the most negative up; k is their count and must be odd.
+trait Numeral {
+    const DIGITS: &'static str;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct Snafu5;
+
+impl Numeral for Snafu5 {
+    const DIGITS: &'static str = "=-012";
+}
+
+// Least significant digit first, with no trailing zeros; zero has no digits at all.
+struct Balanced<S> {
+    digits: Vec<i8>,
+    numeral: PhantomData<S>,
+}
+
+type Snafu = Balanced<Snafu5>;
+
+impl<S: Numeral> Balanced<S> {
+    const BASE: i64 = {
+        assert!(
+            S::DIGITS.len() % 2 == 1,
+            "a balanced numeral needs an odd number of digits"
+        );
+        S::DIGITS.len() as i64
+    };
+    const HALF: i64 = Self::BASE / 2;
+
+    fn zero() -> Self {
+        Self::from_digits(Vec::new())
+    }
+
+    fn from_digits(mut digits: Vec<i8>) -> Self {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        Self {
+            digits,
+            numeral: PhantomData,
+        }
+    }
+
+    // Splits any number into a balanced digit and what carries to the next place.
+    fn split(v: i64) -> (i8, i64) {
+        let digit = (v + Self::HALF).rem_euclid(Self::BASE) - Self::HALF;
+        (digit as i8, (v - digit) / Self::BASE)
+    }
+
+    // Sets each place to `places[i]` and carries whatever doesn't fit.
+    fn from_places(places: impl IntoIterator<Item = i64>) -> Self {
+        let mut digits = Vec::new();
+        let mut carry = 0;
+        for v in places {
+            let (digit, c) = Self::split(v + carry);
+            digits.push(digit);
+            carry = c;
+        }
+        while carry != 0 {
+            let (digit, c) = Self::split(carry);
+            digits.push(digit);
+            carry = c;
+        }
+        Self::from_digits(digits)
+    }
+
+    fn signum(&self) -> i8 {
+        self.digits.last().map_or(0, |d| d.signum())
+    }
+}
+
+impl<S> Clone for Balanced<S> {
+    fn clone(&self) -> Self {
+        Self {
+            digits: self.digits.clone(),
+            numeral: PhantomData,
+        }
+    }
+}
+
+impl<S> fmt::Debug for Balanced<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Balanced").field(&self.digits).finish()
+    }
+}
+
+impl<S> PartialEq for Balanced<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.digits == other.digits
+    }
+}
+
+impl<S> Eq for Balanced<S> {}
+
+impl<S: Numeral> Ord for Balanced<S> {
+    // With no trailing zeros, a longer number is further from zero, and numbers of the same
+    // length order like their digits read from the top.
+    fn cmp(&self, other: &Self) -> Ordering {
+        let sign = self.signum();
+        sign.cmp(&other.signum()).then_with(|| {
+            let len = self.digits.len().cmp(&other.digits.len());
+            let len = if sign < 0 { len.reverse() } else { len };
+            len.then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+        })
+    }
+}
+
+impl<S: Numeral> PartialOrd for Balanced<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
-fn unsnafu(s: &str) -> i64 {
-    let mut n = 0;
-    for c in s.chars() {
-        let digit = match c {
-            '=' => -2,
-            '-' => -1,
-            '0' => 0,
-            '1' => 1,
-            '2' => 2,
-            _ => panic!(),
-        };
-        n = (n * 5) + digit;
+impl<S: Numeral> Neg for &Balanced<S> {
+    type Output = Balanced<S>;
+
+    fn neg(self) -> Balanced<S> {
+        Balanced::from_digits(self.digits.iter().map(|d| -d).collect())
     }
-    n
 }
 
-fn snafu(n: N) -> String {
-    let mut place = 1;
-    let mut m = -2;
-    while nine_nine_nine(place * 5) < n {
-        place *= 5;
-        m -= 2 * place;
+impl<S: Numeral> Add for &Balanced<S> {
+    type Output = Balanced<S>;
+
+    fn add(self, rhs: Self) -> Balanced<S> {
+        let len = self.digits.len().max(rhs.digits.len());
+        let at = |n: &Balanced<S>, i: usize| *n.digits.get(i).unwrap_or(&0) as i64;
+        Balanced::from_places((0..len).map(|i| at(self, i) + at(rhs, i)))
     }
+}
 
-    let mut s = String::new();
+impl<S: Numeral> Sub for &Balanced<S> {
+    type Output = Balanced<S>;
+
+    fn sub(self, rhs: Self) -> Balanced<S> {
+        self + &-rhs
+    }
+}
 
-    while place > 0 {
-        let mut digit = -2;
-        while m + place <= n {
-            m += place;
-            digit += 1;
+impl<S: Numeral> Mul for &Balanced<S> {
+    type Output = Balanced<S>;
+
+    fn mul(self, rhs: Self) -> Balanced<S> {
+        if self.digits.is_empty() || rhs.digits.is_empty() {
+            return Balanced::zero();
+        }
+        let mut places = vec![0; self.digits.len() + rhs.digits.len()];
+        for (i, &a) in self.digits.iter().enumerate() {
+            for (j, &b) in rhs.digits.iter().enumerate() {
+                places[i + j] += a as i64 * b as i64;
+            }
         }
-        place /= 5;
+        Balanced::from_places(places)
+    }
+}
 
-        s.push(match digit {
-            -2 => '=',
-            -1 => '-',
-            0 => '0',
-            1 => '1',
-            2 => '2',
-            x => panic!("{x}"),
-        });
+impl<S: Numeral> Sum for Balanced<S> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |acc, n| &acc + &n)
     }
-    s
 }
 
-fn nine_nine_nine(mut place: N) -> N {
-    let mut n = 2 * place;
-    while place > 0 {
-        assert!(place % 5 == 0 || place == 1, "{place}");
-        place /= 5;
-        n -= 2 * place;
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseError {
+    Empty,
+    BadDigit { pos: usize, found: char },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "no digits"),
+            Self::BadDigit { pos, found } => write!(f, "at {pos}: {found:?} isn't a digit"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl<S: Numeral> FromStr for Balanced<S> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        let mut digits = Vec::with_capacity(s.len());
+        for (pos, found) in s.char_indices() {
+            let i = S::DIGITS
+                .chars()
+                .position(|d| d == found)
+                .ok_or(ParseError::BadDigit { pos, found })?;
+            digits.push((i as i64 - Self::HALF) as i8);
+        }
+        digits.reverse();
+        Ok(Self::from_digits(digits))
+    }
+}
+
+impl<S: Numeral> Display for Balanced<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let glyph = |d: i64| S::DIGITS.chars().nth((d + Self::HALF) as usize).unwrap();
+        if self.digits.is_empty() {
+            return write!(f, "{}", glyph(0));
+        }
+        for &d in self.digits.iter().rev() {
+            write!(f, "{}", glyph(d as i64))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct OverflowError;
+
+impl Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "number out of range")
+    }
+}
+
+impl std::error::Error for OverflowError {}
+
+impl<S: Numeral> From<i128> for Balanced<S> {
+    fn from(mut n: i128) -> Self {
+        let base = Self::BASE as i128;
+        let half = Self::HALF as i128;
+        let mut digits = Vec::new();
+        while n != 0 {
+            // n = q * base + r; a digit above half borrows one from the next place, and q is
+            // small enough that q + 1 can't overflow
+            let (q, r) = (n.div_euclid(base), n.rem_euclid(base));
+            let (digit, next) = if r > half {
+                (r - base, q.checked_add(1))
+            } else {
+                (r, Some(q))
+            };
+            digits.push(digit as i8);
+            n = next.expect("|n| / base fits");
+        }
+        Self::from_digits(digits)
+    }
+}
+
+impl<S: Numeral> TryFrom<&Balanced<S>> for i128 {
+    type Error = OverflowError;
+
+    fn try_from(n: &Balanced<S>) -> Result<Self, Self::Error> {
+        let base = Balanced::<S>::BASE as i128;
+        n.digits.iter().rev().try_fold(0i128, |acc, &d| {
+            let d = d as i128;
+            acc.checked_mul(base)
+                .and_then(|a| a.checked_add(d))
+                // acc * base can overflow by less than a digit when the sum still fits, as at
+                // i128::MIN; moving one base from acc over to the digit keeps both in range
+                .or_else(|| {
+                    let s = acc.signum();
+                    (acc - s).checked_mul(base)?.checked_add(d + s * base)
+                })
+                .ok_or(OverflowError)
+        })
     }
-    n
+}
+
+macro_rules! convert {
+    ($($t:ty),*) => {$(
+        impl<S: Numeral> From<$t> for Balanced<S> {
+            fn from(n: $t) -> Self {
+                Self::from(n as i128)
+            }
+        }
+
+        impl<S: Numeral> TryFrom<&Balanced<S>> for $t {
+            type Error = OverflowError;
+
+            fn try_from(n: &Balanced<S>) -> Result<Self, Self::Error> {
+                <$t>::try_from(i128::try_from(n)?).map_err(|_| OverflowError)
+            }
+        }
+    )*};
+}
+
+convert!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+fn parse(s: &'static str) -> In {
+    s.parse().unwrap_or_else(|e| panic!("{e} in {s:?}"))
 }
 
 fn part1(n: &[In]) -> Out {
-    let sum = n.iter().copied().map(unsnafu).sum::<N>();
-    snafu(sum)
+    n.iter().cloned().sum::<Snafu>().to_string()
 }
 
 fn part2(_: &[In]) -> Out {
@@ -75,3 +308,101 @@ fn part2(_: &[In]) -> Out {
 }
 
 util::register!(parse, part1, part2);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_extremes() {
+        for n in [
+            0,
+            1,
+            -1,
+            2,
+            3,
+            -3,
+            2022,
+            -2022,
+            i128::MAX,
+            i128::MIN,
+            i128::MAX - 2,
+        ] {
+            let s = Snafu::from(n);
+            assert_eq!(i128::try_from(&s), Ok(n), "{s}");
+        }
+        assert_eq!(Snafu::from(2022).to_string(), "1=11-2");
+        assert_eq!(u8::try_from(&Snafu::from(256)), Err(OverflowError));
+    }
+
+    #[test]
+    fn orders_like_integers() {
+        let ns = [
+            -200i128, -126, -125, -13, -12, -3, -2, -1, 0, 1, 2, 3, 12, 13, 125, 126, 200,
+        ];
+        for &a in &ns {
+            for &b in &ns {
+                assert_eq!(Snafu::from(a).cmp(&Snafu::from(b)), a.cmp(&b), "{a} vs {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic_matches_integers() {
+        let ns = [
+            -3000i64, -126, -7, -2, -1, 0, 1, 2, 3, 12, 13, 62, 63, 314, 4890,
+        ];
+        for &a in &ns {
+            for &b in &ns {
+                let (x, y) = (Snafu::from(a), Snafu::from(b));
+                assert_eq!(i64::try_from(&(&x + &y)), Ok(a + b), "{a} + {b}");
+                assert_eq!(i64::try_from(&(&x - &y)), Ok(a - b), "{a} - {b}");
+                assert_eq!(i64::try_from(&(&x * &y)), Ok(a * b), "{a} * {b}");
+            }
+        }
+        // results are normalised, so equal numbers compare equal whatever made them
+        let five = Snafu::from(5);
+        assert_eq!(&five - &five, Snafu::zero());
+        assert_eq!(
+            &(&five * &Snafu::from(-2)) + &Snafu::from(10),
+            Snafu::zero()
+        );
+        assert_eq!((&five - &five).to_string(), "0");
+    }
+
+    #[test]
+    fn sums_and_multiplies_big_numbers() {
+        let big = Snafu::from(i64::MAX);
+        let square = &big * &big;
+        assert_eq!(
+            i128::try_from(&square),
+            Ok(i64::MAX as i128 * i64::MAX as i128)
+        );
+        assert_eq!(i64::try_from(&square), Err(OverflowError));
+        let total: Snafu = ["1=-0-2", "12111", "2=0=", "21", "2=01"]
+            .iter()
+            .map(|s| s.parse::<Snafu>().unwrap())
+            .sum();
+        assert_eq!(i64::try_from(&total), Ok(1747 + 906 + 198 + 11 + 201));
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        assert_eq!("".parse::<Snafu>(), Err(ParseError::Empty));
+        assert_eq!(
+            "1=3-".parse::<Snafu>(),
+            Err(ParseError::BadDigit { pos: 2, found: '3' })
+        );
+        assert_eq!(
+            "12 ".parse::<Snafu>(),
+            Err(ParseError::BadDigit { pos: 2, found: ' ' })
+        );
+        assert_eq!(
+            "2=é".parse::<Snafu>().unwrap_err().to_string(),
+            "at 2: 'é' isn't a digit"
+        );
+        assert_eq!(ParseError::Empty.to_string(), "no digits");
+        assert_eq!("00".parse::<Snafu>(), Ok(Snafu::zero()));
+        assert_eq!("-".parse::<Snafu>().map(|n| i8::try_from(&n)), Ok(Ok(-1)));
+    }
+}
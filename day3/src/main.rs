@@ -1,7 +1,6 @@
 #![cfg_attr(test, feature(test))]
-#![feature(array_chunks)]
 
-use std::collections::HashSet;
+use util::bitset::{self, BitSet};
 
 type In = &'static str;
 type Out = u32;
@@ -10,34 +9,28 @@ fn parse(s: &'static str) -> In {
     s
 }
 
-fn set(s: &str) -> HashSet<char> {
-    s.chars().collect()
+fn set(s: &str) -> BitSet {
+    s.chars().map(|c| bitset::letter(c).unwrap()).collect()
 }
 
-fn priority(c: char) -> u32 {
-    match c {
-        'a'..='z' => (c as u32 - 'a' as u32) + 1,
-        'A'..='Z' => (c as u32 - 'A' as u32) + 27,
-        _ => 0,
-    }
+// Letters index a to z then A to Z, one short of their priorities.
+fn priority(set: BitSet) -> u32 {
+    set.iter().next().unwrap() as u32 + 1
 }
 
 fn part1(n: &[In]) -> Out {
     let mut sum = 0;
     for s in n {
         let (a, b) = s.split_at(s.len() / 2);
-        let ab = &set(a) & &set(b);
-        sum += priority(ab.into_iter().next().unwrap());
+        sum += priority(set(a) & set(b));
     }
     sum
 }
 
 fn part2(n: &[In]) -> Out {
     let mut sum = 0;
-    for [a, b, c] in n.array_chunks() {
-        let ab = &set(a) & &set(b);
-        let abc = &ab & &set(c);
-        sum += priority(abc.into_iter().next().unwrap());
+    for [a, b, c] in n.as_chunks().0 {
+        sum += priority(set(a) & set(b) & set(c));
     }
     sum
 }
@@ -1,11 +1,13 @@
 #![cfg_attr(test, feature(test))]
 
+use std::fmt::{self, Display};
+
 type In = Input;
 type Out = String;
 
 #[derive(Debug, Clone)]
 struct Input {
-    stacks: Vec<Vec<char>>,
+    stacks: Stacks,
     moves: Vec<Move>,
 }
 
@@ -16,6 +18,143 @@ struct Move {
     to: usize,
 }
 
+impl Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.amount, self.from, self.to)
+    }
+}
+
+// Bottom crate first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stacks(Vec<Vec<char>>);
+
+impl Stacks {
+    fn tops(&self) -> String {
+        self.0
+            .iter()
+            .map(|s| s.last().copied().unwrap_or(' '))
+            .collect()
+    }
+}
+
+// The same drawing as the puzzle input, numbers underneath.
+impl Display for Stacks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let height = self.0.iter().map(Vec::len).max().unwrap_or(0);
+        for level in (0..height).rev() {
+            let cells = self.0.iter().map(|s| match s.get(level) {
+                Some(c) => format!("[{c}]"),
+                None => String::from("   "),
+            });
+            writeln!(f, "{}", Vec::from_iter(cells).join(" "))?;
+        }
+        let numbers = (1..=self.0.len()).map(|i| format!(" {i} "));
+        write!(f, "{}", Vec::from_iter(numbers).join(" "))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Crane {
+    // one crate at a time, so a group lands upside down
+    CrateMover9000,
+    // the whole group at once
+    CrateMover9001,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Problem {
+    NoSuchStack(usize),
+    NotEnough { have: usize },
+}
+
+#[derive(Debug, Clone)]
+struct MoveError {
+    index: usize,
+    mv: Move,
+    problem: Problem,
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move #{} ({}): ", self.index + 1, self.mv)?;
+        match self.problem {
+            Problem::NoSuchStack(n) => write!(f, "there is no stack {n}"),
+            Problem::NotEnough { have } => write!(f, "stack {} only has {have}", self.mv.from),
+        }
+    }
+}
+
+impl Crane {
+    fn check(&self, stacks: &Stacks, mv: Move) -> Result<(), Problem> {
+        for n in [mv.from, mv.to] {
+            if n == 0 || n > stacks.0.len() {
+                return Err(Problem::NoSuchStack(n));
+            }
+        }
+        let have = stacks.0[mv.from - 1].len();
+        if have < mv.amount {
+            return Err(Problem::NotEnough { have });
+        }
+        Ok(())
+    }
+
+    fn apply(&self, stacks: &mut Stacks, mv: Move) -> Result<(), Problem> {
+        self.check(stacks, mv)?;
+        if mv.from == mv.to {
+            // picked up and put straight back, whichever way the crane lifts
+            return Ok(());
+        }
+
+        let src = &mut stacks.0[mv.from - 1];
+        let mut group = src.split_off(src.len() - mv.amount);
+        if *self == Crane::CrateMover9000 {
+            group.reverse();
+        }
+        stacks.0[mv.to - 1].extend(group);
+        Ok(())
+    }
+}
+
+// Runs the moves a few at a time, so the stacks can be looked at in between.
+struct Yard<'a> {
+    stacks: Stacks,
+    moves: &'a [Move],
+    crane: Crane,
+    next: usize,
+}
+
+impl<'a> Yard<'a> {
+    fn new(input: &'a Input, crane: Crane) -> Self {
+        Self {
+            stacks: input.stacks.clone(),
+            moves: &input.moves,
+            crane,
+            next: 0,
+        }
+    }
+
+    // Makes up to `k` more moves. A move that can't be done is left as the next one, with the
+    // stacks as they were before it.
+    fn apply(&mut self, k: usize) -> Result<(), MoveError> {
+        let end = self.moves.len().min(self.next.saturating_add(k));
+        while self.next < end {
+            let (index, mv) = (self.next, self.moves[self.next]);
+            self.crane
+                .apply(&mut self.stacks, mv)
+                .map_err(|problem| MoveError { index, mv, problem })?;
+            self.next += 1;
+        }
+        Ok(())
+    }
+}
+
+// Runs the moves in order, stopping at the first that can't be done.
+fn rearrange(input: &Input, crane: Crane) -> Result<Stacks, MoveError> {
+    let mut yard = Yard::new(input, crane);
+    yard.apply(usize::MAX)?;
+    Ok(yard.stacks)
+}
+
 fn parse(s: &'static str) -> In {
     let mut lines = s.lines();
 
@@ -49,32 +188,125 @@ fn parse(s: &'static str) -> In {
         moves.push(mv);
     }
 
-    Input { stacks, moves }
+    Input {
+        stacks: Stacks(stacks),
+        moves,
+    }
 }
 
 fn part1(n: &In) -> Out {
-    let mut state = n.clone();
+    let stacks = rearrange(n, Crane::CrateMover9000);
+    stacks.unwrap_or_else(|e| panic!("{e}")).tops()
+}
 
-    for m in state.moves {
-        for _ in 0..m.amount {
-            let cr = state.stacks[m.from - 1].pop().unwrap();
-            state.stacks[m.to - 1].push(cr);
-        }
+fn part2(n: &In) -> Out {
+    let stacks = rearrange(n, Crane::CrateMover9001);
+    stacks.unwrap_or_else(|e| panic!("{e}")).tops()
+}
+
+util::register!(parse, part1, part2, @alt);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DRAWING: &str = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 ";
+
+    fn example() -> Input {
+        parse(TEST_INPUT)
     }
 
-    state.stacks.iter().map(|s| s.last().unwrap()).collect()
-}
+    fn mv(amount: usize, from: usize, to: usize) -> Move {
+        Move { amount, from, to }
+    }
 
-fn part2(n: &In) -> Out {
-    let mut state = n.clone();
+    #[test]
+    fn draws_like_the_puzzle() {
+        let input = example();
+        assert_eq!(input.stacks.to_string(), DRAWING);
+        assert_eq!(parse(DRAWING).stacks, input.stacks);
+        assert_eq!(
+            Stacks(vec![vec![], vec!['A']]).to_string(),
+            "    [A]\n 1   2 "
+        );
+        assert_eq!(Stacks(vec![vec![]; 2]).to_string(), " 1   2 ");
+    }
 
-    for m in state.moves {
-        let src = &mut state.stacks[m.from - 1];
-        let group = src.split_off(src.len() - m.amount);
-        state.stacks[m.to - 1].extend(group);
+    #[test]
+    fn redraws_after_each_move() {
+        let input = example();
+        let mut yard = Yard::new(&input, Crane::CrateMover9000);
+        yard.apply(1).unwrap();
+        assert_eq!(
+            yard.stacks.to_string(),
+            "[D]        \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 "
+        );
+        yard.apply(1).unwrap();
+        assert_eq!(
+            yard.stacks.to_string(),
+            "        [Z]\n        [N]\n    [C] [D]\n    [M] [P]\n 1   2   3 "
+        );
+        assert_eq!(yard.next, 2);
+        yard.apply(usize::MAX).unwrap();
+        assert_eq!(yard.next, 4);
+        assert_eq!(yard.stacks.tops(), "CMZ");
+        yard.apply(1).unwrap();
+        assert_eq!(yard.stacks.tops(), "CMZ");
     }
 
-    state.stacks.iter().map(|s| s.last().unwrap()).collect()
-}
+    #[test]
+    fn reports_missing_stacks() {
+        let mut input = example();
+        input.moves = vec![mv(1, 2, 1), mv(1, 4, 1), mv(1, 1, 2)];
+        let err = rearrange(&input, Crane::CrateMover9001).unwrap_err();
+        assert_eq!(
+            (err.index, err.problem.clone()),
+            (1, Problem::NoSuchStack(4))
+        );
+        assert_eq!(
+            err.to_string(),
+            "move #2 (move 1 from 4 to 1): there is no stack 4"
+        );
 
-util::register!(parse, part1, part2, @alt);
+        input.moves = vec![mv(1, 1, 0)];
+        let err = rearrange(&input, Crane::CrateMover9000).unwrap_err();
+        assert_eq!(err.problem, Problem::NoSuchStack(0));
+    }
+
+    #[test]
+    fn reports_short_stacks() {
+        let mut input = example();
+        input.moves = vec![mv(1, 3, 1), mv(1, 3, 2)];
+        let mut yard = Yard::new(&input, Crane::CrateMover9000);
+        let err = yard.apply(2).unwrap_err();
+        assert_eq!(
+            (err.index, err.problem.clone()),
+            (1, Problem::NotEnough { have: 0 })
+        );
+        assert_eq!(
+            err.to_string(),
+            "move #2 (move 1 from 3 to 2): stack 3 only has 0"
+        );
+        // the first move went through, and the stuck one is still next
+        assert_eq!(yard.stacks.tops(), "PD ");
+        assert_eq!(yard.next, 1);
+
+        input.moves = vec![mv(4, 2, 1)];
+        let err = rearrange(&input, Crane::CrateMover9001).unwrap_err();
+        assert_eq!(err.problem, Problem::NotEnough { have: 3 });
+    }
+
+    #[test]
+    fn moving_onto_the_same_stack_changes_nothing() {
+        let mv = Move {
+            amount: 2,
+            from: 1,
+            to: 1,
+        };
+        for crane in [Crane::CrateMover9000, Crane::CrateMover9001] {
+            let mut stacks = Stacks(vec![vec!['A', 'B', 'C']]);
+            crane.apply(&mut stacks, mv).unwrap();
+            assert_eq!(stacks.0, [['A', 'B', 'C']]);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 #![cfg_attr(test, feature(test))]
 
-use std::collections::HashSet;
+use std::io::{self, BufReader, Read};
 
 type In = &'static str;
 type Out = usize;
@@ -9,20 +9,138 @@ fn parse(s: &'static str) -> In {
     s
 }
 
+// Watches a stream one byte at a time for the last `window` bytes being all different.
+struct Detector {
+    window: usize,
+    // Where each byte was last seen, counting from 1 so 0 can mean never.
+    last_seen: [usize; 256],
+    pos: usize,
+    // Nothing from here on repeats, up to `pos`.
+    start: usize,
+}
+
+impl Detector {
+    fn new(window: usize) -> Self {
+        assert!(window > 0, "an empty window is everywhere");
+        Self {
+            window,
+            last_seen: [0; 256],
+            pos: 0,
+            start: 0,
+        }
+    }
+
+    // Whether a marker ends with `b`; its position is then the bytes read so far.
+    fn push(&mut self, b: u8) -> bool {
+        self.pos += 1;
+        self.start = self.start.max(self.last_seen[b as usize]);
+        self.last_seen[b as usize] = self.pos;
+        self.pos - self.start >= self.window
+    }
+}
+
+// Every position where a marker ends, overlapping ones included.
+fn markers(bytes: impl IntoIterator<Item = u8>, window: usize) -> impl Iterator<Item = usize> {
+    let mut detector = Detector::new(window);
+    bytes
+        .into_iter()
+        .filter_map(move |b| detector.push(b).then_some(detector.pos))
+}
+
+// The same over a reader, buffered, stopping after the first error.
+fn markers_in(r: impl Read, window: usize) -> impl Iterator<Item = io::Result<usize>> {
+    let mut detector = Detector::new(window);
+    let mut failed = false;
+    BufReader::new(r)
+        .bytes()
+        .map_while(move |b| match b {
+            _ if failed => None,
+            Ok(b) => Some(Ok(detector.push(b).then_some(detector.pos))),
+            Err(e) => {
+                failed = true;
+                Some(Err(e))
+            }
+        })
+        .filter_map(Result::transpose)
+}
+
 fn part1(&n: &In) -> Out {
-    n.as_bytes()
-        .windows(4)
-        .position(|w| w.iter().collect::<HashSet<_>>().len() == 4)
-        .unwrap()
-        + 4
+    markers(n.bytes(), 4).next().unwrap()
 }
 
 fn part2(&n: &In) -> Out {
-    n.as_bytes()
-        .windows(14)
-        .position(|w| w.iter().collect::<HashSet<_>>().len() == 14)
-        .unwrap()
-        + 14
+    markers_in(n.as_bytes(), 14).next().unwrap().unwrap()
 }
 
 util::register!(parse, part1, part2, @alt);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hands out `data`, then fails every read after.
+    struct Flaky(&'static [u8]);
+
+    impl Read for Flaky {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::Error::other("gone"));
+            }
+            let n = buf.len().min(self.0.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn finds_every_marker() {
+        assert_eq!(
+            markers(*b"mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4).next(),
+            Some(7)
+        );
+        assert_eq!(Vec::from_iter(markers(*b"abcabd", 3)), [3, 4, 5, 6]);
+        assert_eq!(Vec::from_iter(markers(*b"aabbb", 1)), [1, 2, 3, 4, 5]);
+        assert_eq!(Vec::from_iter(markers(*b"aaaa", 2)), []);
+        assert_eq!(Vec::from_iter(markers(*b"ab", 3)), []);
+    }
+
+    #[test]
+    fn remembers_the_latest_repeat() {
+        // the second `a` repeats one already behind the repeated `b`,
+        // so it mustn't pull the start back and pass "bcba" as a marker
+        assert_eq!(Vec::from_iter(markers(*b"abcbad", 4)), [6]);
+        assert_eq!(Vec::from_iter(markers(*b"abcbad", 3)), [3, 5, 6]);
+    }
+
+    #[test]
+    fn reads_markers_from_a_reader() {
+        let s = "zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw";
+        let from_bytes = Vec::from_iter(markers(s.bytes(), 14));
+        let from_reader = markers_in(s.as_bytes(), 14).collect::<io::Result<Vec<_>>>();
+        assert_eq!(from_reader.unwrap(), from_bytes);
+        assert_eq!(from_bytes.first(), Some(&26));
+    }
+
+    #[test]
+    fn stops_after_an_error() {
+        let mut found = markers_in(Flaky(b"abcd"), 2);
+        assert_eq!(found.next().unwrap().unwrap(), 2);
+        assert_eq!(
+            found
+                .by_ref()
+                .take(2)
+                .map(Result::unwrap)
+                .collect::<Vec<_>>(),
+            [3, 4]
+        );
+        assert!(found.next().unwrap().is_err());
+        assert!(found.next().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "an empty window")]
+    fn refuses_empty_windows() {
+        markers(*b"abc", 0).next();
+    }
+}
@@ -1,6 +1,9 @@
 #![cfg_attr(test, feature(test))]
 
 use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+use util::dot::{Dot, DotWriter, Highlight, Kind};
 
 #[derive(Default)]
 struct Dir {
@@ -12,10 +15,64 @@ impl Dir {
         self.entries.values().map(|e| e.size()).sum()
     }
 
-    pub fn traverse_mut(&mut self, path: &[&'static str]) -> Option<&mut Self> {
+    // Makes any directories along `path` that haven't been seen yet.
+    pub fn make_dirs(&mut self, path: &[&'static str]) -> Result<&mut Self, ShellError> {
+        let mut cwd = self;
+        for seg in path {
+            let ent = cwd
+                .entries
+                .entry(seg)
+                .or_insert_with(|| Entry::Dir(Dir::default()));
+            match ent {
+                Entry::Dir(d) => cwd = d,
+                Entry::File(_) => return Err(ShellError::NotADir(seg)),
+            }
+        }
+        Ok(cwd)
+    }
+
+    fn collect_dirs(&self, path: &str, out: &mut Vec<(String, u64)>) -> u64 {
+        let mut size = 0;
+        for (name, entry) in &self.entries {
+            size += match entry {
+                Entry::File(sz) => *sz,
+                Entry::Dir(d) => d.collect_dirs(&join(path, name), out),
+            };
+        }
+        out.push((path.to_owned(), size));
+        size
+    }
+
+    // Every directory with its total size, sorted by path.
+    pub fn dirs(&self) -> Vec<(String, u64)> {
+        let mut dirs = Vec::new();
+        self.collect_dirs("/", &mut dirs);
+        dirs.sort();
+        dirs
+    }
+
+    // The smallest directory that leaves `needed` free on a disk of size `total` once deleted.
+    // None if there's already enough free, or if the disk can't hold `needed` at all.
+    pub fn smallest_freeing(&self, total: u64, needed: u64) -> Option<String> {
+        let free = total.checked_sub(self.size())?;
+        let missing = needed.saturating_sub(free);
+        if missing == 0 {
+            return None;
+        }
+        self.dirs()
+            .into_iter()
+            .filter(|(_, size)| *size >= missing)
+            .min_by_key(|(_, size)| *size)
+            .map(|(path, _)| path)
+    }
+}
+
+// Lookups and listings by path.
+impl Dir {
+    pub fn traverse(&self, path: &[&str]) -> Option<&Self> {
         let mut cwd = self;
         for seg in path {
-            let ent = cwd.entries.get_mut(seg)?;
+            let ent = cwd.entries.get(*seg)?;
             if let Entry::Dir(d) = ent {
                 cwd = d;
             } else {
@@ -24,6 +81,93 @@ impl Dir {
         }
         Some(cwd)
     }
+
+    pub fn size_of(&self, path: &str) -> Option<u64> {
+        let segs = Vec::from_iter(path.split('/').filter(|s| !s.is_empty()));
+        let (last, parent) = match segs.split_last() {
+            Some((last, parent)) => (last, parent),
+            None => return Some(self.size()),
+        };
+        self.traverse(parent)?.entries.get(*last).map(Entry::size)
+    }
+
+    pub fn du(&self) -> String {
+        let lines = self
+            .dirs()
+            .into_iter()
+            .map(|(path, size)| format!("{size}\t{path}"));
+        Vec::from_iter(lines).join("\n")
+    }
+
+    fn write_tree(&self, depth: usize, out: &mut String) {
+        let mut entries = Vec::from_iter(&self.entries);
+        entries.sort_by_key(|(name, _)| **name);
+
+        for (name, entry) in entries {
+            let indent = "  ".repeat(depth);
+            match entry {
+                Entry::File(size) => {
+                    out.push_str(&format!("{indent}- {name} (file, size={size})\n"))
+                }
+                Entry::Dir(d) => {
+                    out.push_str(&format!("{indent}- {name} (dir)\n"));
+                    d.write_tree(depth + 1, out);
+                }
+            }
+        }
+    }
+
+    pub fn tree(&self) -> String {
+        let mut out = String::from("- / (dir)\n");
+        self.write_tree(1, &mut out);
+        out
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    format!("{}/{name}", path.trim_end_matches('/'))
+}
+
+fn ancestry(path: &str) -> Vec<String> {
+    let mut chain = vec![String::from("/")];
+    for seg in path.split('/').filter(|s| !s.is_empty()) {
+        chain.push(join(chain.last().unwrap(), seg));
+    }
+    chain
+}
+
+impl Dir {
+    fn write_dot_at(&self, path: &str, w: &mut DotWriter) {
+        let mut entries = Vec::from_iter(&self.entries);
+        entries.sort_by_key(|(name, _)| **name);
+
+        for (name, entry) in entries {
+            let child = join(path, name);
+            let shape = match entry {
+                Entry::File(_) => "note",
+                Entry::Dir(_) => "folder",
+            };
+            w.node(&child)
+                .set("label", format!("{name}\n{}", entry.size()))
+                .set("shape", shape);
+            w.edge(path, &child);
+            if let Entry::Dir(d) = entry {
+                d.write_dot_at(&child, w);
+            }
+        }
+    }
+}
+
+impl Dot for Dir {
+    const KIND: Kind = Kind::Digraph;
+
+    fn write_dot(&self, w: &mut DotWriter) {
+        w.graph().set("rankdir", "LR");
+        w.node("/")
+            .set("label", format!("/\n{}", self.size()))
+            .set("shape", "folder");
+        self.write_dot_at("/", w);
+    }
 }
 
 enum Entry {
@@ -40,88 +184,272 @@ impl Entry {
     }
 }
 
-fn make_tree(session: &'static str) -> Dir {
+// Applies a `cd` argument, which may be absolute and have several segments.
+fn change_dir(cwd: &mut Vec<&'static str>, arg: &'static str) {
+    if arg.starts_with('/') {
+        cwd.clear();
+    }
+    for seg in arg.split('/') {
+        match seg {
+            "" | "." => (),
+            ".." => {
+                cwd.pop();
+            }
+            x => cwd.push(x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ShellError {
+    NotACommand(&'static str),
+    UnknownCommand(&'static str),
+    BadEntry(&'static str),
+    // a file where a directory was expected
+    NotADir(&'static str),
+    // a directory listed again as a file
+    IsADir(&'static str),
+}
+
+impl Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotACommand(line) => write!(f, "expected a command, got {line:?}"),
+            Self::UnknownCommand(cmd) => write!(f, "unknown command {cmd:?}"),
+            Self::BadEntry(entry) => write!(f, "bad ls entry {entry:?}"),
+            Self::NotADir(name) => write!(f, "{name} is a file, not a directory"),
+            Self::IsADir(name) => write!(f, "{name} is a directory, not a file"),
+        }
+    }
+}
+
+fn interpret(session: &'static str) -> Result<Dir, ShellError> {
     let mut cwd = vec![];
     let mut root = Dir::default();
-    let mut lines = session.lines().map(|s| s.trim()).peekable();
-    while let Some(cmd) = lines.next() {
-        let cmd = cmd.strip_prefix("$ ").expect("unexpected non-command");
+    let mut lines = session
+        .lines()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .peekable();
+    while let Some(line) = lines.next() {
+        let cmd = line
+            .strip_prefix("$ ")
+            .ok_or(ShellError::NotACommand(line))?;
 
         if let Some(arg) = cmd.strip_prefix("cd ") {
-            match arg {
-                "/" => cwd.clear(),
-                ".." => {
-                    cwd.pop();
-                }
-                x => cwd.push(x),
-            }
+            change_dir(&mut cwd, arg.trim());
         } else if cmd == "ls" {
-            let cwd_dir = root.traverse_mut(&cwd).unwrap();
+            let cwd_dir = root.make_dirs(&cwd)?;
 
-            while let Some(entry) = lines.peek() {
+            while let Some(&entry) = lines.peek() {
                 if entry.starts_with('$') {
                     break;
                 }
 
-                let (foo, name) = entry.split_once(' ').unwrap();
-                if foo == "dir" {
-                    cwd_dir.entries.insert(name, Entry::Dir(Default::default()));
+                let (kind, name) = entry.split_once(' ').ok_or(ShellError::BadEntry(entry))?;
+                if kind == "dir" {
+                    // keep whatever we already know about it
+                    let ent = cwd_dir
+                        .entries
+                        .entry(name)
+                        .or_insert_with(|| Entry::Dir(Default::default()));
+                    if let Entry::File(_) = ent {
+                        return Err(ShellError::NotADir(name));
+                    }
                 } else {
-                    let size = foo.parse::<u64>().unwrap();
+                    let size = kind.parse::<u64>().or(Err(ShellError::BadEntry(entry)))?;
+                    if let Some(Entry::Dir(_)) = cwd_dir.entries.get(name) {
+                        return Err(ShellError::IsADir(name));
+                    }
                     cwd_dir.entries.insert(name, Entry::File(size));
                 }
 
                 lines.next();
             }
         } else {
-            panic!("unknown command");
+            return Err(ShellError::UnknownCommand(cmd));
         }
     }
-    root
+    Ok(root)
+}
+
+fn make_tree(session: &'static str) -> Dir {
+    interpret(session).unwrap_or_else(|e| panic!("{e}"))
 }
 
 fn part1(root: &Dir) -> u64 {
-    let mut stack = vec![root];
-    let mut size_sum = 0;
-    while let Some(dir) = stack.pop() {
-        for entry in dir.entries.values() {
-            if let Entry::Dir(d) = entry {
-                stack.push(d);
-            }
-        }
-        let sz = dir.size();
-        if sz <= 100000 {
-            size_sum += sz;
-        }
+    // `--opt show=du` or `--opt show=tree` lists the filesystem on stderr
+    match util::opt("show").as_deref() {
+        Some("du") => eprintln!("{}", root.du()),
+        Some("tree") => eprint!("{}", root.tree()),
+        None => {}
+        Some(s) => panic!("unknown listing {s:?}"),
     }
 
-    size_sum
+    let counted = Vec::from_iter(root.dirs().into_iter().filter(|(_, sz)| *sz <= 100000));
+
+    if util::dot::is_enabled() {
+        let highlight = Highlight::nodes(counted.iter().map(|(path, _)| path));
+        util::dot::export(root, &highlight).unwrap();
+    }
+
+    counted.iter().map(|(_, sz)| sz).sum()
 }
 
 fn part2(root: &Dir) -> u64 {
-    const TOTAL: u64 = 70000000;
-    const NEEDED: u64 = 30000000;
+    // nothing to delete if there's already room
+    let Some(path) = root.smallest_freeing(70000000, 30000000) else {
+        return 0;
+    };
 
-    let already_avail: u64 = TOTAL - root.size();
-    let additional_needed: u64 = NEEDED - already_avail;
+    if util::dot::is_enabled() {
+        util::dot::export(root, &Highlight::path(ancestry(&path))).unwrap();
+    }
 
-    let mut stack = vec![root];
+    root.size_of(&path).unwrap()
+}
 
-    let mut result = u64::MAX;
+util::register!(make_tree, part1, part2, @alt);
 
-    while let Some(dir) = stack.pop() {
-        for entry in dir.entries.values() {
-            if let Entry::Dir(d) = entry {
-                stack.push(d);
-            }
-        }
-        let sz = dir.size();
-        if sz >= additional_needed {
-            result = u64::min(result, sz);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_the_example() {
+        let root = make_tree(TEST_INPUT);
+        assert!(root.traverse(&["a", "e"]).is_some());
+        assert!(root.traverse(&["a", "f"]).is_none());
+        assert!(root.traverse(&["x"]).is_none());
+        assert_eq!(root.traverse(&[]).map(Dir::size), Some(48381165));
+
+        assert_eq!(root.size_of("/"), Some(48381165));
+        assert_eq!(root.size_of("/a/e"), Some(584));
+        assert_eq!(root.size_of("/a/f"), Some(29116));
+        assert_eq!(root.size_of("a/e/i"), Some(584));
+        assert_eq!(root.size_of("/a/nope"), None);
+        assert_eq!(root.size_of("/b.txt/x"), None);
+
+        assert_eq!(root.du(), "48381165\t/\n94853\t/a\n584\t/a/e\n24933642\t/d");
+        assert_eq!(
+            root.tree(),
+            "- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"
+        );
     }
 
-    result
-}
+    #[test]
+    fn frees_only_what_is_missing() {
+        let root = make_tree(TEST_INPUT);
+        assert_eq!(
+            root.smallest_freeing(70000000, 30000000),
+            Some(String::from("/d"))
+        );
+        // 21618835 is free, so that much needs nothing deleted
+        assert_eq!(root.smallest_freeing(70000000, 21618835), None);
+        assert_eq!(
+            root.smallest_freeing(70000000, 21618836),
+            Some(String::from("/a/e"))
+        );
+        // more than the disk holds, or already more on it than fits
+        assert_eq!(root.smallest_freeing(70000000, 70000001), None);
+        assert_eq!(root.smallest_freeing(1000, 10), None);
+    }
 
-util::register!(make_tree, part1, part2, @alt);
+    #[test]
+    fn follows_absolute_and_multi_segment_paths() {
+        let root = make_tree(
+            "$ cd /a/b
+            $ ls
+            10 x
+            $ cd ../../c/./d
+            $ ls
+            20 y
+            $ cd /a
+            $ cd b/..
+            $ ls
+            30 z",
+        );
+        assert_eq!(
+            root.dirs(),
+            [
+                ("/".into(), 60),
+                ("/a".into(), 40),
+                ("/a/b".into(), 10),
+                ("/c".into(), 20),
+                ("/c/d".into(), 20)
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_ls_keeps_what_it_knew() {
+        let root = make_tree(
+            "$ cd /
+            $ ls
+            dir a
+            5 f
+            $ cd a
+            $ ls
+            7 g
+            $ cd /
+            $ ls
+            dir a
+            5 f",
+        );
+        assert_eq!(root.size_of("/a"), Some(7));
+        assert_eq!(root.size(), 12);
+    }
+
+    #[test]
+    fn files_seen_before_their_directory() {
+        // `a` and `a/b` are listed before anything lists them as directories
+        let root = make_tree(
+            "$ cd /a/b
+            $ ls
+            3 f
+            $ cd /
+            $ ls
+            dir a
+            $ cd a
+            $ ls
+            dir b
+            4 g",
+        );
+        assert_eq!(root.size_of("/a/b/f"), Some(3));
+        assert_eq!(root.size_of("/a"), Some(7));
+    }
+
+    #[test]
+    fn reports_bad_sessions() {
+        let cases = [
+            ("ls", ShellError::NotACommand("ls")),
+            ("$ rm -rf /", ShellError::UnknownCommand("rm -rf /")),
+            ("$ ls\nnonsense", ShellError::BadEntry("nonsense")),
+            ("$ ls\nbig f", ShellError::BadEntry("big f")),
+            ("$ ls\n1 f\n$ cd f\n$ ls", ShellError::NotADir("f")),
+            ("$ ls\n1 f\n$ ls\ndir f", ShellError::NotADir("f")),
+            // a file can't quietly replace everything under a directory
+            (
+                "$ cd /d\n$ ls\n5 x\n$ cd /\n$ ls\n9 d",
+                ShellError::IsADir("d"),
+            ),
+        ];
+        for (session, err) in cases {
+            assert_eq!(interpret(session).err(), Some(err), "{session}");
+        }
+    }
+}
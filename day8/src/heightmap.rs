@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use util::Pos;
+
+pub type P = Pos<usize>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Dir {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl Dir {
+    pub const ORTHOGONAL: [Dir; 4] = [Dir::North, Dir::East, Dir::South, Dir::West];
+    pub const ALL: [Dir; 8] = [
+        Dir::North,
+        Dir::NorthEast,
+        Dir::East,
+        Dir::SouthEast,
+        Dir::South,
+        Dir::SouthWest,
+        Dir::West,
+        Dir::NorthWest,
+    ];
+
+    pub fn delta(self) -> (isize, isize) {
+        match self {
+            Dir::North => (0, -1),
+            Dir::NorthEast => (1, -1),
+            Dir::East => (1, 0),
+            Dir::SouthEast => (1, 1),
+            Dir::South => (0, 1),
+            Dir::SouthWest => (-1, 1),
+            Dir::West => (-1, 0),
+            Dir::NorthWest => (-1, -1),
+        }
+    }
+}
+
+// What a tree sees looking one way: how many trees until the view is blocked (or the edge is
+// reached), and whether it's the edge.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Sight {
+    pub distance: usize,
+    pub to_edge: bool,
+}
+
+pub struct Heightmap {
+    width: usize,
+    height: usize,
+    cells: Vec<i8>,
+}
+
+impl Heightmap {
+    pub fn new(rows: &[Vec<i8>]) -> Self {
+        let width = rows.first().map_or(0, Vec::len);
+        assert!(rows.iter().all(|r| r.len() == width), "ragged heightmap");
+        Self {
+            width,
+            height: rows.len(),
+            cells: rows.concat(),
+        }
+    }
+
+    pub fn get(&self, p: P) -> i8 {
+        self.cells[p.y * self.width + p.x]
+    }
+
+    fn step(&self, p: P, d: Dir) -> Option<P> {
+        let (dx, dy) = d.delta();
+        let x = p.x.checked_add_signed(dx).filter(|&x| x < self.width)?;
+        let y = p.y.checked_add_signed(dy).filter(|&y| y < self.height)?;
+        Some(P::new(x, y))
+    }
+
+    fn back(d: Dir) -> Dir {
+        Dir::ALL[(d as usize + 4) % 8]
+    }
+
+    // Every maximal run of cells along `d`, each in the order `d` walks it.
+    fn lines(&self, d: Dir) -> impl Iterator<Item = Vec<P>> + '_ {
+        let cells = (0..self.height).flat_map(move |y| (0..self.width).map(move |x| P::new(x, y)));
+        cells
+            .filter(move |&p| self.step(p, Self::back(d)).is_none())
+            .map(move |start| std::iter::successors(Some(start), |&p| self.step(p, d)).collect())
+    }
+
+    // Looks along each of `dirs` from every tree at once, a monotonic stack per line.
+    pub fn analyze(&self, dirs: &[Dir]) -> Sightlines {
+        let mut sights = HashMap::new();
+        for &d in dirs {
+            let mut sight = vec![
+                Sight {
+                    distance: 0,
+                    to_edge: true
+                };
+                self.cells.len()
+            ];
+            for line in self.lines(d) {
+                // Indices into `line` of trees ahead that nothing nearer hides, nearest on top.
+                let mut stack: Vec<usize> = Vec::new();
+                for (i, &p) in line.iter().enumerate().rev() {
+                    let h = self.get(p);
+                    while stack.last().is_some_and(|&j| self.get(line[j]) < h) {
+                        stack.pop();
+                    }
+                    sight[p.y * self.width + p.x] = match stack.last() {
+                        Some(&j) => Sight {
+                            distance: j - i,
+                            to_edge: false,
+                        },
+                        None => Sight {
+                            distance: line.len() - 1 - i,
+                            to_edge: true,
+                        },
+                    };
+                    stack.push(i);
+                }
+            }
+            sights.insert(d, sight);
+        }
+        Sightlines {
+            width: self.width,
+            height: self.height,
+            sights,
+        }
+    }
+
+    // The same question for any observer: someone `eye` tall standing at `from`, which need not
+    // hold a tree of that height. Neither part needs it.
+    #[allow(dead_code)]
+    pub fn look(&self, from: P, eye: i8, d: Dir) -> Sight {
+        let mut distance = 0;
+        let mut p = from;
+        while let Some(next) = self.step(p, d) {
+            distance += 1;
+            if self.get(next) >= eye {
+                return Sight {
+                    distance,
+                    to_edge: false,
+                };
+            }
+            p = next;
+        }
+        Sight {
+            distance,
+            to_edge: true,
+        }
+    }
+}
+
+pub struct Sightlines {
+    width: usize,
+    height: usize,
+    sights: HashMap<Dir, Vec<Sight>>,
+}
+
+impl Sightlines {
+    pub fn sight(&self, p: P, d: Dir) -> Sight {
+        self.sights[&d][p.y * self.width + p.x]
+    }
+
+    // Seen from outside the grid along any analysed direction.
+    pub fn is_visible(&self, p: P) -> bool {
+        self.sights
+            .values()
+            .any(|s| s[p.y * self.width + p.x].to_edge)
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = P> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| P::new(x, y)))
+    }
+
+    pub fn visible(&self) -> impl Iterator<Item = P> + '_ {
+        self.cells().filter(|&p| self.is_visible(p))
+    }
+
+    // The highest-scoring tree, given what it sees in each of `dirs` in that order; ties go to
+    // the first in reading order.
+    pub fn best<S: Ord>(&self, dirs: &[Dir], score: impl Fn(&[Sight]) -> S) -> Option<(P, S)> {
+        let mut sights = Vec::with_capacity(dirs.len());
+        let mut best: Option<(P, S)> = None;
+        for p in self.cells() {
+            sights.clear();
+            sights.extend(dirs.iter().map(|&d| self.sight(p, d)));
+            let s = score(&sights);
+            if best.as_ref().is_none_or(|(_, b)| s > *b) {
+                best = Some((p, s));
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FOREST: [&str; 5] = ["30373", "25512", "65332", "33549", "35390"];
+
+    fn heightmap(rows: &[&str]) -> Heightmap {
+        let rows = Vec::from_iter(
+            rows.iter()
+                .map(|r| Vec::from_iter(r.bytes().map(|b| (b - b'0') as i8))),
+        );
+        Heightmap::new(&rows)
+    }
+
+    #[test]
+    fn analysis_matches_looking() {
+        let map = heightmap(&FOREST);
+        let sightlines = map.analyze(&Dir::ALL);
+        for p in sightlines.cells() {
+            for d in Dir::ALL {
+                assert_eq!(
+                    sightlines.sight(p, d),
+                    map.look(p, map.get(p), d),
+                    "{p:?} {d:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn looks_from_anywhere() {
+        let map = heightmap(&FOREST);
+        let sight = |distance, to_edge| Sight { distance, to_edge };
+        // from the middle of "65332", someone 3 tall sees the next tree east and no further,
+        // and someone 7 tall sees over the whole row to the west
+        let middle = P::new(2, 2);
+        assert_eq!(map.look(middle, 3, Dir::East), sight(1, false));
+        assert_eq!(map.look(middle, 7, Dir::West), sight(2, true));
+        assert_eq!(map.look(middle, 6, Dir::West), sight(2, false));
+        assert_eq!(map.look(P::new(0, 0), 9, Dir::North), sight(0, true));
+        assert_eq!(map.look(P::new(0, 0), 4, Dir::SouthEast), sight(1, false));
+    }
+
+    #[test]
+    fn visible_and_best() {
+        let sightlines = heightmap(&FOREST).analyze(&Dir::ORTHOGONAL);
+        assert_eq!(sightlines.visible().count(), 21);
+        let scenic = |s: &[Sight]| s.iter().map(|s| s.distance).product::<usize>();
+        assert_eq!(
+            sightlines.best(&Dir::ORTHOGONAL, scenic),
+            Some((P::new(2, 3), 8))
+        );
+        // ties go to the first tree
+        assert_eq!(
+            sightlines.best(&Dir::ORTHOGONAL, |_| 0),
+            Some((P::new(0, 0), 0))
+        );
+        assert!(heightmap(&[])
+            .analyze(&Dir::ALL)
+            .best(&Dir::ALL, |_| 0)
+            .is_none());
+    }
+}
@@ -2,15 +2,41 @@
 
 use std::collections::HashSet;
 
+use util::sim::{hash_of, Named, Simulation};
+
 type In = Step;
 type Out = usize;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum Dir {
     Up,
     Left,
     Down,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Dir {
+    fn delta(self) -> (i32, i32) {
+        match self {
+            Dir::Up => (0, -1),
+            Dir::Left => (-1, 0),
+            Dir::Down => (0, 1),
+            Dir::Right => (1, 0),
+            Dir::UpLeft => (-1, -1),
+            Dir::UpRight => (1, -1),
+            Dir::DownLeft => (-1, 1),
+            Dir::DownRight => (1, 1),
+        }
+    }
+
+    fn is_diagonal(self) -> bool {
+        let (dx, dy) = self.delta();
+        dx != 0 && dy != 0
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -20,14 +46,19 @@ struct Step {
 }
 
 fn parse(s: &'static str) -> In {
-    let dir = match s.as_bytes()[0] {
-        b'U' => Dir::Up,
-        b'D' => Dir::Down,
-        b'L' => Dir::Left,
-        b'R' => Dir::Right,
-        _ => panic!(),
+    let (dir, count) = s.split_once(' ').unwrap();
+    let dir = match dir {
+        "U" => Dir::Up,
+        "D" => Dir::Down,
+        "L" => Dir::Left,
+        "R" => Dir::Right,
+        "UL" => Dir::UpLeft,
+        "UR" => Dir::UpRight,
+        "DL" => Dir::DownLeft,
+        "DR" => Dir::DownRight,
+        _ => panic!("unknown direction {dir:?}"),
     };
-    let count = s[2..].parse().unwrap();
+    let count = count.parse().unwrap();
     Step { dir, count }
 }
 
@@ -38,12 +69,9 @@ struct Pos {
 }
 
 fn take_step(head: &mut Pos, dir: Dir) {
-    match dir {
-        Dir::Up => head.y -= 1,
-        Dir::Left => head.x -= 1,
-        Dir::Down => head.y += 1,
-        Dir::Right => head.x += 1,
-    }
+    let (dx, dy) = dir.delta();
+    head.x += dx;
+    head.y += dy;
 }
 
 fn catch_up(head: &Pos, tail: &mut Pos) {
@@ -62,43 +90,207 @@ fn catch_up(head: &Pos, tail: &mut Pos) {
     }
 }
 
-fn part1(n: &[In]) -> Out {
-    let mut head = Pos::default();
-    let mut tail = Pos::default();
+// Knot 0 is the head and the last knot is the tail.
+struct Rope {
+    moves: Vec<Dir>,
+    t: usize,
+    knots: Vec<Pos>,
+    visited: Vec<HashSet<Pos>>,
+}
+
+impl Rope {
+    fn new(n: &[In], knots: usize, diagonal: bool) -> Self {
+        if let Some(step) = n.iter().find(|step| step.dir.is_diagonal() && !diagonal) {
+            panic!(
+                "diagonal move {:?} with diagonal head moves turned off",
+                step.dir
+            );
+        }
+        let moves = n
+            .iter()
+            .flat_map(|step| std::iter::repeat_n(step.dir, step.count as usize))
+            .collect();
+        let knots = vec![Pos::default(); knots];
+        let visited = vec![HashSet::from([Pos::default()]); knots.len()];
+        Self {
+            moves,
+            t: 0,
+            knots,
+            visited,
+        }
+    }
+
+    fn tail(&self) -> usize {
+        self.knots.len() - 1
+    }
 
-    let mut visited = HashSet::new();
-    visited.insert(tail);
+    fn visited(&self, knot: usize) -> &HashSet<Pos> {
+        &self.visited[knot]
+    }
 
-    for &step in n {
-        for _ in 0..step.count {
-            take_step(&mut head, step.dir);
-            catch_up(&head, &mut tail);
-            visited.insert(tail);
+    // Everywhere `knot` has been, with the rope where it is now on top.
+    fn render_trail(&self, knot: usize) -> String {
+        let trail = &self.visited[knot];
+        let points = || trail.iter().chain(&self.knots);
+        let x0 = points().map(|p| p.x).min().unwrap();
+        let x1 = points().map(|p| p.x).max().unwrap();
+        let y0 = points().map(|p| p.y).min().unwrap();
+        let y1 = points().map(|p| p.y).max().unwrap();
+
+        let mut s = String::new();
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let pos = Pos { x, y };
+                let c = match self.knots.iter().position(|&l| l == pos) {
+                    Some(0) => 'H',
+                    Some(i) => char::from_digit(i as u32 % 10, 10).unwrap(),
+                    None if pos == Pos::default() => 's',
+                    None if trail.contains(&pos) => '#',
+                    None => '.',
+                };
+                s.push(c);
+            }
+            s.push('\n');
         }
+        s
     }
+}
 
-    visited.len()
+// Each item is where the knots are after one more single-square move of the head.
+impl Iterator for Rope {
+    type Item = Vec<Pos>;
+
+    fn next(&mut self) -> Option<Vec<Pos>> {
+        let dir = *self.moves.get(self.t)?;
+        take_step(&mut self.knots[0], dir);
+        for i in 1..self.knots.len() {
+            let [.., head, tail] = &mut self.knots[..=i] else {
+                panic!()
+            };
+            catch_up(head, tail);
+        }
+        for (visited, knot) in self.visited.iter_mut().zip(&self.knots) {
+            visited.insert(*knot);
+        }
+        self.t += 1;
+        Some(self.knots.clone())
+    }
 }
 
-fn part2(n: &[In]) -> Out {
-    let mut links = vec![Pos::default(); 10];
+impl Simulation for Rope {
+    fn step(&mut self) {
+        self.next();
+    }
 
-    let mut visited = HashSet::new();
+    fn is_done(&self) -> bool {
+        self.t == self.moves.len()
+    }
 
-    visited.insert(links[0]);
+    fn render(&self) -> String {
+        self.render_trail(self.tail())
+    }
 
-    for &step in n {
-        for _ in 0..step.count {
-            take_step(&mut links[9], step.dir);
-            for i in (1..links.len()).rev() {
-                let [.., tail, head] = &mut links[..=i] else { panic!() };
-                catch_up(head, tail);
-            }
-            visited.insert(links[0]);
-        }
+    fn fingerprint(&self) -> u64 {
+        hash_of(&self.knots)
     }
+}
+
+// For `until <name>` with `--step`.
+fn predicates<'p>() -> Vec<Named<'p, Rope>> {
+    let mut seen = 1;
+    vec![
+        // the tail reaches a cell it hasn't been to before
+        (
+            "new-cell",
+            Box::new(move |rope: &Rope| {
+                let n = rope.visited(rope.tail()).len();
+                std::mem::replace(&mut seen, n) < n
+            }),
+        ),
+        // no knot is sitting on top of the one before it
+        (
+            "stretched",
+            Box::new(|rope: &Rope| rope.knots.windows(2).all(|w| w[0] != w[1])),
+        ),
+    ]
+}
+
+fn tail_visits(n: &[In], knots: usize) -> Out {
+    let mut rope = Rope::new(n, knots, false);
+    util::sim::run_with(&mut rope, predicates());
+    rope.visited(rope.tail()).len()
+}
 
-    visited.len()
+fn part1(n: &[In]) -> Out {
+    tail_visits(n, 2)
+}
+
+fn part2(n: &[In]) -> Out {
+    tail_visits(n, 10)
 }
 
 util::register!(parse, part1, part2);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::sim::Stepper;
+
+    fn steps(s: &'static str) -> Vec<Step> {
+        Vec::from_iter(s.split(", ").map(parse))
+    }
+
+    fn at(x: i32, y: i32) -> Pos {
+        Pos { x, y }
+    }
+
+    #[test]
+    fn follows_diagonal_heads() {
+        let mut rope = Rope::new(&steps("DR 3"), 3, true);
+        assert_eq!(rope.next(), Some(vec![at(1, 1), at(0, 0), at(0, 0)]));
+        assert_eq!(rope.next(), Some(vec![at(2, 2), at(1, 1), at(0, 0)]));
+        assert_eq!(rope.next(), Some(vec![at(3, 3), at(2, 2), at(1, 1)]));
+        assert_eq!(rope.next(), None);
+        assert_eq!(
+            rope.visited(rope.tail()),
+            &HashSet::from([at(0, 0), at(1, 1)])
+        );
+    }
+
+    #[test]
+    fn diagonals_mix_with_straight_moves() {
+        // the head doubles back past the tail, which never needs to move
+        let mut rope = Rope::new(&steps("UL 1, R 2, DL 1"), 2, true);
+        util::sim::run(&mut rope);
+        assert_eq!(rope.knots, [at(0, 0), at(0, 0)]);
+        assert_eq!(rope.visited(1).len(), 1);
+
+        let mut rope = Rope::new(&steps("R 1, UR 2"), 2, true);
+        util::sim::run(&mut rope);
+        assert_eq!(rope.knots, [at(3, -2), at(2, -2)]);
+    }
+
+    #[test]
+    fn steps_to_named_predicates() {
+        let mut rope = Rope::new(&steps("R 4, U 4"), 3, false);
+        let mut stepper = Stepper::with_predicates(&mut rope, predicates());
+        let mut out = Vec::new();
+        let input = "?\nu new-cell\nu new-cell\nu stretched\nc\n";
+        stepper.interactive(input.as_bytes(), &mut out).unwrap();
+        // the tail first moves on the head's third step and again on its fourth; the knots
+        // are all apart from then on, so `stretched` holds on the very next step
+        assert_eq!(stepper.steps(), 5);
+        assert_eq!(rope.knots, [at(4, -1), at(3, 0), at(2, 0)]);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("predicates: new-cell, stretched"));
+        for s in ["step 3 [", "step 4 [", "step 5 ["] {
+            assert!(out.contains(s), "{s:?} missing from {out}");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "diagonal move UpLeft")]
+    fn refuses_diagonals_when_off() {
+        Rope::new(&steps("R 1, UL 1"), 2, false);
+    }
+}
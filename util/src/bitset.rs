@@ -0,0 +1,519 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Sub, SubAssign};
+
+const BITS: usize = u64::BITS as usize;
+
+// `a`..=`z` are 0..26 and `A`..=`Z` are 26..52, which fits in one word.
+pub fn letter(c: char) -> Option<usize> {
+    match c {
+        'a'..='z' => Some(c as usize - 'a' as usize),
+        'A'..='Z' => Some(c as usize - 'A' as usize + 26),
+        _ => None,
+    }
+}
+
+// Hands out indices to names so sets of them can be bitsets.
+#[derive(Debug, Clone)]
+pub struct Indexer<K> {
+    keys: Vec<K>,
+    index: HashMap<K, usize>,
+}
+
+impl<K: Clone + Eq + Hash> Indexer<K> {
+    // Indices follow the order the keys come in; repeats keep their first index.
+    pub fn new(keys: impl IntoIterator<Item = K>) -> Self {
+        let mut this = Self {
+            keys: Vec::new(),
+            index: HashMap::new(),
+        };
+        for k in keys {
+            this.insert(k);
+        }
+        this
+    }
+
+    pub fn insert(&mut self, key: K) -> usize {
+        if let Some(&i) = self.index.get(&key) {
+            return i;
+        }
+        self.keys.push(key.clone());
+        self.index.insert(key, self.keys.len() - 1);
+        self.keys.len() - 1
+    }
+
+    pub fn index<Q>(&self, key: &Q) -> usize
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index[key]
+    }
+
+    pub fn key(&self, i: usize) -> &K {
+        &self.keys[i]
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl<K: PartialEq> PartialEq for Indexer<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.keys == other.keys
+    }
+}
+
+// Set bits of a run of words, lowest first.
+pub struct Iter<'a> {
+    words: &'a [u64],
+    base: usize,
+    word: u64,
+}
+
+impl<'a> Iter<'a> {
+    fn new(words: &'a [u64]) -> Self {
+        let (&word, words) = words.split_first().unwrap_or((&0, &[]));
+        Self {
+            words,
+            base: 0,
+            word,
+        }
+    }
+}
+
+impl Iterator for Iter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.word == 0 {
+            let (&word, rest) = self.words.split_first()?;
+            self.word = word;
+            self.words = rest;
+            self.base += BITS;
+        }
+        let bit = self.word.trailing_zeros() as usize;
+        self.word &= self.word - 1;
+        Some(self.base + bit)
+    }
+}
+
+// Subset order, where sets that each have something the other lacks don't compare.
+fn subset_cmp(a: impl Iterator<Item = u64>, b: impl Iterator<Item = u64>) -> Option<Ordering> {
+    let (mut subset, mut superset) = (true, true);
+    for (a, b) in a.zip(b) {
+        subset &= a & !b == 0;
+        superset &= b & !a == 0;
+    }
+    match (subset, superset) {
+        (true, true) => Some(Ordering::Equal),
+        (true, false) => Some(Ordering::Less),
+        (false, true) => Some(Ordering::Greater),
+        (false, false) => None,
+    }
+}
+
+// A set of the numbers below `64 * WORDS`, small enough to copy around.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BitSet<const WORDS: usize = 1>([u64; WORDS]);
+
+impl<const WORDS: usize> BitSet<WORDS> {
+    pub const CAPACITY: usize = BITS * WORDS;
+
+    pub fn new() -> Self {
+        Self([0; WORDS])
+    }
+
+    // Panics if `i` doesn't fit, rather than quietly dropping it.
+    pub fn insert(&mut self, i: usize) -> bool {
+        assert!(i < Self::CAPACITY, "{i} doesn't fit in a BitSet<{WORDS}>");
+        let (w, bit) = (i / BITS, 1 << (i % BITS));
+        let new = self.0[w] & bit == 0;
+        self.0[w] |= bit;
+        new
+    }
+
+    pub fn remove(&mut self, i: usize) -> bool {
+        if i >= Self::CAPACITY {
+            return false;
+        }
+        let (w, bit) = (i / BITS, 1 << (i % BITS));
+        let had = self.0[w] & bit != 0;
+        self.0[w] &= !bit;
+        had
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        i < Self::CAPACITY && self.0[i / BITS] >> (i % BITS) & 1 == 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&w| w == 0)
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(&self.0)
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.0.iter().zip(&other.0).all(|(a, b)| a & !b == 0)
+    }
+
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.0.iter().zip(&other.0).all(|(a, b)| a & b == 0)
+    }
+}
+
+impl<const WORDS: usize> Default for BitSet<WORDS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const WORDS: usize> PartialOrd for BitSet<WORDS> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        subset_cmp(self.0.iter().copied(), other.0.iter().copied())
+    }
+}
+
+impl<const WORDS: usize> Debug for BitSet<WORDS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<const WORDS: usize> FromIterator<usize> for BitSet<WORDS> {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut this = Self::new();
+        this.extend(iter);
+        this
+    }
+}
+
+impl<const WORDS: usize> Extend<usize> for BitSet<WORDS> {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        for i in iter {
+            self.insert(i);
+        }
+    }
+}
+
+impl<'a, const WORDS: usize> IntoIterator for &'a BitSet<WORDS> {
+    type Item = usize;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+macro_rules! fixed_op {
+    ($($op:ident $f:ident $assign:ident $assign_f:ident |$a:ident, $b:ident| $e:expr;)*) => {$(
+        impl<const WORDS: usize> $assign for BitSet<WORDS> {
+            fn $assign_f(&mut self, rhs: Self) {
+                for (word, &$b) in self.0.iter_mut().zip(&rhs.0) {
+                    let $a = *word;
+                    *word = $e;
+                }
+            }
+        }
+
+        impl<const WORDS: usize> $op for BitSet<WORDS> {
+            type Output = Self;
+
+            fn $f(mut self, rhs: Self) -> Self {
+                self.$assign_f(rhs);
+                self
+            }
+        }
+    )*};
+}
+
+fixed_op! {
+    BitOr bitor BitOrAssign bitor_assign |a, b| a | b;
+    BitAnd bitand BitAndAssign bitand_assign |a, b| a & b;
+    BitXor bitxor BitXorAssign bitxor_assign |a, b| a ^ b;
+    Sub sub SubAssign sub_assign |a, b| a & !b;
+}
+
+// A set of numbers with no upper bound, growing to fit the largest.
+#[derive(Clone, Default)]
+pub struct BitVecSet(Vec<u64>);
+
+impl BitVecSet {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn with_capacity(n: usize) -> Self {
+        Self(Vec::with_capacity(n.div_ceil(BITS)))
+    }
+
+    // Words without the zeros on the end, which don't change what's in the set.
+    fn words(&self) -> &[u64] {
+        let len = self.0.iter().rposition(|&w| w != 0).map_or(0, |i| i + 1);
+        &self.0[..len]
+    }
+
+    fn word(&self, w: usize) -> u64 {
+        self.0.get(w).copied().unwrap_or(0)
+    }
+
+    pub fn insert(&mut self, i: usize) -> bool {
+        let (w, bit) = (i / BITS, 1 << (i % BITS));
+        if self.0.len() <= w {
+            self.0.resize(w + 1, 0);
+        }
+        let new = self.0[w] & bit == 0;
+        self.0[w] |= bit;
+        new
+    }
+
+    pub fn remove(&mut self, i: usize) -> bool {
+        let Some(word) = self.0.get_mut(i / BITS) else {
+            return false;
+        };
+        let bit = 1 << (i % BITS);
+        let had = *word & bit != 0;
+        *word &= !bit;
+        had
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.word(i / BITS) >> (i % BITS) & 1 == 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words().is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(&self.0)
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.0
+            .iter()
+            .enumerate()
+            .all(|(w, a)| a & !other.word(w) == 0)
+    }
+
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.0.iter().zip(&other.0).all(|(a, b)| a & b == 0)
+    }
+}
+
+impl PartialEq for BitVecSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.words() == other.words()
+    }
+}
+
+impl Eq for BitVecSet {}
+
+impl Hash for BitVecSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.words().hash(state);
+    }
+}
+
+impl PartialOrd for BitVecSet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let len = self.0.len().max(other.0.len());
+        subset_cmp(
+            (0..len).map(|w| self.word(w)),
+            (0..len).map(|w| other.word(w)),
+        )
+    }
+}
+
+impl Debug for BitVecSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl FromIterator<usize> for BitVecSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut this = Self::new();
+        this.extend(iter);
+        this
+    }
+}
+
+impl Extend<usize> for BitVecSet {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        for i in iter {
+            self.insert(i);
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a BitVecSet {
+    type Item = usize;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+macro_rules! growable_op {
+    ($($op:ident $f:ident $assign:ident $assign_f:ident |$a:ident, $b:ident| $e:expr;)*) => {$(
+        impl $assign<&BitVecSet> for BitVecSet {
+            fn $assign_f(&mut self, rhs: &BitVecSet) {
+                if self.0.len() < rhs.0.len() {
+                    self.0.resize(rhs.0.len(), 0);
+                }
+                for (w, word) in self.0.iter_mut().enumerate() {
+                    let ($a, $b) = (*word, rhs.word(w));
+                    *word = $e;
+                }
+            }
+        }
+
+        impl $op for &BitVecSet {
+            type Output = BitVecSet;
+
+            fn $f(self, rhs: Self) -> BitVecSet {
+                let mut out = self.clone();
+                out.$assign_f(rhs);
+                out
+            }
+        }
+    )*};
+}
+
+growable_op! {
+    BitOr bitor BitOrAssign bitor_assign |a, b| a | b;
+    BitAnd bitand BitAndAssign bitand_assign |a, b| a & b;
+    BitXor bitxor BitXorAssign bitxor_assign |a, b| a ^ b;
+    Sub sub SubAssign sub_assign |a, b| a & !b;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash(value: &impl Hash) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn letters_and_indexer() {
+        assert_eq!(letter('a'), Some(0));
+        assert_eq!(letter('Z'), Some(51));
+        assert_eq!(letter('!'), None);
+
+        let mut ix = Indexer::new(["AA", "BB", "AA"]);
+        assert_eq!(ix.len(), 2);
+        assert_eq!(ix.insert("CC"), 2);
+        assert_eq!(ix.insert("BB"), 1);
+        assert_eq!((ix.index("CC"), *ix.key(0)), (2, "AA"));
+    }
+
+    #[test]
+    fn fixed_sets() {
+        let mut a = BitSet::<2>::from_iter([0, 63, 64, 127]);
+        assert!(!a.insert(64));
+        assert!(a.remove(63) && !a.remove(63) && !a.remove(500));
+        assert_eq!(Vec::from_iter(&a), [0, 64, 127]);
+        assert_eq!(
+            (a.len(), a.contains(127), a.contains(128)),
+            (3, true, false)
+        );
+
+        let b = BitSet::<2>::from_iter([1, 64]);
+        assert_eq!(Vec::from_iter(&(a | b)), [0, 1, 64, 127]);
+        assert_eq!(Vec::from_iter(&(a & b)), [64]);
+        assert_eq!(Vec::from_iter(&(a ^ b)), [0, 1, 127]);
+        assert_eq!(Vec::from_iter(&(a - b)), [0, 127]);
+        a -= a;
+        assert!(a.is_empty() && a == BitSet::default());
+        assert_eq!(format!("{b:?}"), "{1, 64}");
+    }
+
+    #[test]
+    #[should_panic(expected = "128 doesn't fit")]
+    fn fixed_sets_refuse_overflow() {
+        BitSet::<2>::new().insert(128);
+    }
+
+    #[test]
+    fn growable_sets() {
+        let mut a = BitVecSet::from_iter([3, 300]);
+        let b = BitVecSet::from_iter([3, 70]);
+        assert_eq!(Vec::from_iter(&(&a | &b)), [3, 70, 300]);
+        assert_eq!(Vec::from_iter(&(&a & &b)), [3]);
+        assert_eq!(Vec::from_iter(&(&a ^ &b)), [70, 300]);
+        assert_eq!(Vec::from_iter(&(&b - &a)), [70]);
+        a |= &b;
+        assert_eq!(a.len(), 3);
+        a &= &BitVecSet::new();
+        assert!(a.is_empty());
+        assert!(!BitVecSet::new().contains(1000) && !BitVecSet::new().remove(1000));
+    }
+
+    #[test]
+    fn trailing_zero_words_dont_count() {
+        let mut a = BitVecSet::from_iter([5]);
+        a.insert(1000);
+        a.remove(1000);
+        let b = BitVecSet::from_iter([5]);
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_eq!(a.partial_cmp(&b), Some(Ordering::Equal));
+
+        let mut empty = BitVecSet::with_capacity(500);
+        empty.insert(400);
+        empty.remove(400);
+        assert!(empty.is_empty() && empty == BitVecSet::new());
+        assert!(empty.is_subset(&BitVecSet::new()) && empty.is_disjoint(&b));
+    }
+
+    #[test]
+    fn subset_order() {
+        let small = BitSet::<1>::from_iter([1]);
+        let big = BitSet::<1>::from_iter([1, 2]);
+        let other = BitSet::<1>::from_iter([3]);
+        assert!(small < big);
+        assert_eq!(big.partial_cmp(&small), Some(Ordering::Greater));
+        let same = BitSet::<1>::from_iter([1]);
+        assert!(small <= same && big >= same);
+        assert_eq!(small.partial_cmp(&same), Some(Ordering::Equal));
+        assert_eq!(small.partial_cmp(&other), None);
+        assert!(small.is_subset(&big) && big.is_superset(&small) && small.is_disjoint(&other));
+
+        let small = BitVecSet::from_iter([1]);
+        let big = BitVecSet::from_iter([1, 200]);
+        assert!(small < big && big.partial_cmp(&small) == Some(Ordering::Greater));
+        assert_eq!(big.partial_cmp(&BitVecSet::from_iter([200, 300])), None);
+        assert!(!big.is_subset(&small) && big.is_superset(&small));
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Write as _};
+use std::io;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::part_path;
+
+static OUTPUT: OnceLock<PathBuf> = OnceLock::new();
+
+// Set by the runner when `--dot <path>` is passed.
+pub fn enable(path: impl Into<PathBuf>) {
+    let _ = OUTPUT.set(path.into());
+}
+
+pub fn is_enabled() -> bool {
+    OUTPUT.get().is_some()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Kind {
+    Graph,
+    Digraph,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Attrs(Vec<(&'static str, String)>);
+
+impl Attrs {
+    pub fn set(&mut self, key: &'static str, value: impl Display) -> &mut Self {
+        let value = value.to_string();
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((key, value)),
+        }
+        self
+    }
+
+    fn write(&self, out: &mut String) {
+        if self.0.is_empty() {
+            return;
+        }
+        let attrs = Vec::from_iter(self.0.iter().map(|(k, v)| format!("{k}={}", quote(v))));
+        write!(out, " [{}]", attrs.join(",")).unwrap();
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut q = String::with_capacity(s.len() + 2);
+    q.push('"');
+    for c in s.chars() {
+        match c {
+            '\\' => q.push_str("\\\\"),
+            '"' => q.push_str("\\\""),
+            '\n' => q.push_str("\\n"),
+            c => q.push(c),
+        }
+    }
+    q.push('"');
+    q
+}
+
+#[derive(Debug, Default)]
+pub struct DotWriter {
+    nodes: Vec<(String, Attrs)>,
+    edges: Vec<(String, String, Attrs)>,
+    graph: Attrs,
+}
+
+impl DotWriter {
+    pub fn node(&mut self, id: impl Display) -> &mut Attrs {
+        self.nodes.push((id.to_string(), Attrs::default()));
+        &mut self.nodes.last_mut().unwrap().1
+    }
+
+    pub fn edge(&mut self, src: impl Display, dst: impl Display) -> &mut Attrs {
+        let edge = (src.to_string(), dst.to_string(), Attrs::default());
+        self.edges.push(edge);
+        &mut self.edges.last_mut().unwrap().2
+    }
+
+    pub fn graph(&mut self) -> &mut Attrs {
+        &mut self.graph
+    }
+}
+
+// Nodes and edges to draw in red, e.g. the part of the graph a solution went through.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Highlight {
+    nodes: HashSet<String>,
+    edges: HashSet<(String, String)>,
+}
+
+impl Highlight {
+    pub fn nodes<T: Display>(ids: impl IntoIterator<Item = T>) -> Self {
+        let nodes = ids.into_iter().map(|id| id.to_string()).collect();
+        Self {
+            nodes,
+            edges: HashSet::new(),
+        }
+    }
+
+    // Every node along `ids`, and the edges between consecutive ones.
+    pub fn path<T: Display>(ids: impl IntoIterator<Item = T>) -> Self {
+        let ids = Vec::from_iter(ids.into_iter().map(|id| id.to_string()));
+        let edges = ids
+            .windows(2)
+            .map(|w| (w[0].clone(), w[1].clone()))
+            .collect();
+        let nodes = ids.into_iter().collect();
+        Self { nodes, edges }
+    }
+
+    pub fn with_edge(mut self, src: impl Display, dst: impl Display) -> Self {
+        self.edges.insert((src.to_string(), dst.to_string()));
+        self
+    }
+
+    fn has_node(&self, id: &str) -> bool {
+        self.nodes.contains(id)
+    }
+
+    fn has_edge(&self, kind: Kind, src: &str, dst: &str) -> bool {
+        let key = |a: &str, b: &str| (a.to_owned(), b.to_owned());
+        self.edges.contains(&key(src, dst))
+            || (kind == Kind::Graph && self.edges.contains(&key(dst, src)))
+    }
+}
+
+pub trait Dot {
+    const KIND: Kind = Kind::Graph;
+
+    fn write_dot(&self, w: &mut DotWriter);
+
+    fn dot(&self) -> String {
+        self.dot_highlighted(&Highlight::default())
+    }
+
+    fn dot_highlighted(&self, highlight: &Highlight) -> String {
+        let mut w = DotWriter::default();
+        self.write_dot(&mut w);
+        render(Self::KIND, w, highlight)
+    }
+}
+
+fn render(kind: Kind, w: DotWriter, highlight: &Highlight) -> String {
+    let (keyword, arrow) = match kind {
+        Kind::Graph => ("graph", "--"),
+        Kind::Digraph => ("digraph", "->"),
+    };
+
+    let mut out = String::new();
+    writeln!(out, "{keyword} G {{").unwrap();
+
+    for (k, v) in &w.graph.0 {
+        writeln!(out, "\t{k}={};", quote(v)).unwrap();
+    }
+
+    for (id, mut attrs) in w.nodes {
+        if highlight.has_node(&id) {
+            attrs
+                .set("color", "red")
+                .set("fontcolor", "red")
+                .set("penwidth", 2);
+        }
+        write!(out, "\t{}", quote(&id)).unwrap();
+        attrs.write(&mut out);
+        writeln!(out, ";").unwrap();
+    }
+
+    let mut done_edges = HashSet::new();
+
+    for (src, dst, mut attrs) in w.edges {
+        if kind == Kind::Graph && done_edges.contains(&(dst.clone(), src.clone())) {
+            continue;
+        }
+
+        if highlight.has_edge(kind, &src, &dst) {
+            attrs.set("color", "red").set("penwidth", 2);
+        }
+        write!(out, "\t{} {arrow} {}", quote(&src), quote(&dst)).unwrap();
+        attrs.write(&mut out);
+        writeln!(out, ";").unwrap();
+
+        done_edges.insert((src, dst));
+    }
+
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+// Writes the graph to the `--dot` path, if there is one.
+pub fn export<G: Dot + ?Sized>(graph: &G, highlight: &Highlight) -> io::Result<()> {
+    match OUTPUT.get() {
+        Some(path) => std::fs::write(part_path(path), graph.dot_highlighted(highlight)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Edges<const DIRECTED: bool>(&'static [(&'static str, &'static str)]);
+
+    impl<const DIRECTED: bool> Dot for Edges<DIRECTED> {
+        const KIND: Kind = if DIRECTED { Kind::Digraph } else { Kind::Graph };
+
+        fn write_dot(&self, w: &mut DotWriter) {
+            for (src, dst) in self.0 {
+                w.edge(src, dst);
+            }
+        }
+    }
+
+    #[test]
+    fn quotes_escapes() {
+        assert_eq!(quote("a"), r#""a""#);
+        assert_eq!(quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote(r"C:\dir"), r#""C:\\dir""#);
+        // the backslash the quote brings mustn't be doubled again
+        assert_eq!(quote(r#"\""#), r#""\\\"""#);
+        assert_eq!(quote("two\nlines"), r#""two\nlines""#);
+    }
+
+    #[test]
+    fn renders_nodes_and_attributes() {
+        let mut w = DotWriter::default();
+        w.graph().set("rankdir", "LR");
+        w.node("a\\b").set("label", "size \"1\"").set("label", 2);
+        w.edge("a\\b", "c");
+        let out = render(Kind::Digraph, w, &Highlight::default());
+        let expected = "digraph G {\n\trankdir=\"LR\";\n\t\"a\\\\b\" [label=\"2\"];\n\t\"a\\\\b\" -> \"c\";\n}\n";
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn undirected_edges_draw_once() {
+        let graph = Edges::<false>(&[("a", "b"), ("b", "a"), ("b", "c")]);
+        assert_eq!(
+            graph.dot(),
+            "graph G {\n\t\"a\" -- \"b\";\n\t\"b\" -- \"c\";\n}\n"
+        );
+        let digraph = Edges::<true>(&[("a", "b"), ("b", "a")]);
+        assert_eq!(
+            digraph.dot(),
+            "digraph G {\n\t\"a\" -> \"b\";\n\t\"b\" -> \"a\";\n}\n"
+        );
+    }
+
+    #[test]
+    fn highlights_paths() {
+        let mut w = DotWriter::default();
+        for id in ["a", "b", "c"] {
+            w.node(id);
+        }
+        w.edge("b", "a");
+        w.edge("b", "c");
+        let out = render(Kind::Graph, w, &Highlight::path(["a", "b"]));
+        let lines = Vec::from_iter(out.lines().skip(1));
+        assert_eq!(
+            lines[0],
+            "\t\"a\" [color=\"red\",fontcolor=\"red\",penwidth=\"2\"];"
+        );
+        assert_eq!(lines[2], "\t\"c\";");
+        // either direction matches in an undirected graph
+        assert_eq!(lines[3], "\t\"b\" -- \"a\" [color=\"red\",penwidth=\"2\"];");
+        assert_eq!(lines[4], "\t\"b\" -- \"c\";");
+    }
+}
@@ -1,6 +1,16 @@
 use std::fmt::{Debug, Display};
 use std::ops::{Add, AddAssign};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+
+pub mod bitset;
+pub mod dot;
+pub mod ocr;
+pub mod record;
+pub mod sim;
+pub mod svg;
 
 pub fn p<T>(s: &str) -> T
 where
@@ -79,6 +89,60 @@ pub fn parse_input_lines<T, F: FnMut(&'static str) -> T>(input_data: &'static st
     input_data.lines().map(str::trim).map(f).collect()
 }
 
+static PART: AtomicU8 = AtomicU8::new(0);
+
+fn set_part(part: u8) {
+    PART.store(part, Ordering::Relaxed);
+}
+
+// `out.gif` becomes `out.part1.gif` while part 1 is running, and so on.
+pub(crate) fn part_path(path: &Path) -> PathBuf {
+    match PART.load(Ordering::Relaxed) {
+        0 => path.to_owned(),
+        part => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let mut name = format!("{stem}.part{part}");
+            if let Some(ext) = path.extension() {
+                name = format!("{name}.{}", ext.to_string_lossy());
+            }
+            path.with_file_name(name)
+        }
+    }
+}
+
+static OPTIONS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+fn set_opt(setting: &str) {
+    let (key, value) = setting
+        .split_once('=')
+        .expect("--opt needs a key=value setting");
+    OPTIONS
+        .lock()
+        .unwrap()
+        .push((key.to_owned(), value.to_owned()));
+}
+
+// A day's own setting, from `--opt key=value`; the last one given wins.
+pub fn opt(key: &str) -> Option<String> {
+    let options = OPTIONS.lock().unwrap();
+    let value = options.iter().rev().find(|(k, _)| k == key);
+    value.map(|(_, v)| v.clone())
+}
+
+fn parse_flags() {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--record" => record::enable(args.next().expect("--record needs a path")),
+            "--step" => sim::enable(),
+            "--dot" => dot::enable(args.next().expect("--dot needs a path")),
+            "--svg" => svg::enable(args.next().expect("--svg needs a path")),
+            "--opt" => set_opt(&args.next().expect("--opt needs a key=value setting")),
+            _ => eprintln!("unknown flag: {arg}"),
+        }
+    }
+}
+
 pub fn run<Parser, Part1, Part2, In, Out>(
     input_data: &'static str,
     parser: Parser,
@@ -90,11 +154,15 @@ pub fn run<Parser, Part1, Part2, In, Out>(
     Part2: FnOnce(&[In]) -> Out,
     Out: Display,
 {
+    parse_flags();
+
     let input = parse_input_lines(input_data, parser);
 
+    set_part(1);
     let output1 = part1(&input);
     println!("{output1}");
 
+    set_part(2);
     let output2 = part2(&input);
     println!("{output2}");
 }
@@ -110,11 +178,15 @@ pub fn run_alt<Parser, Part1, Part2, In, Out>(
     Part2: FnOnce(&In) -> Out,
     Out: Display,
 {
+    parse_flags();
+
     let input = parser(input_data);
 
+    set_part(1);
     let output1 = part1(&input);
     println!("{output1}");
 
+    set_part(2);
     let output2 = part2(&input);
     println!("{output2}");
 }
@@ -177,6 +249,50 @@ macro_rules! register {
         $crate::register!($parser, $part1, $part2, run_alt, test_alt);
     };
 
+    // The parts also take a params argument: `$puzzle` for the real input, `$example` in tests.
+    ($parser:expr, $part1:expr, $part2:expr, @params($puzzle:expr, $example:expr)) => {
+        const INPUT: &str = include_str!("../input.txt");
+
+        fn main() {
+            $crate::run(
+                INPUT,
+                $parser,
+                |n| $part1(n, &$puzzle),
+                |n| $part2(n, &$puzzle),
+            );
+        }
+
+        #[cfg(test)]
+        extern crate test;
+
+        const TEST_INPUT: &str = include_str!("../test.txt");
+        const TEST_OUTPUT: &str = include_str!("../test.out.txt");
+
+        #[cfg(test)]
+        #[test]
+        fn test_part1() {
+            $crate::test(
+                TEST_INPUT,
+                TEST_OUTPUT,
+                $parser,
+                |n| $part1(n, &$example),
+                false,
+            );
+        }
+
+        #[cfg(test)]
+        #[test]
+        fn test_part2() {
+            $crate::test(
+                TEST_INPUT,
+                TEST_OUTPUT,
+                $parser,
+                |n| $part2(n, &$example),
+                true,
+            );
+        }
+    };
+
     ($parser:expr, $part1:expr, $part2:expr, $run:ident, $test:ident) => {
         const INPUT: &str = include_str!("../input.txt");
 
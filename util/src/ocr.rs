@@ -0,0 +1,273 @@
+use std::fmt::{self, Display};
+
+pub struct Font {
+    pub width: usize,
+    pub height: usize,
+    pub pitch: usize,
+    glyphs: &'static [(char, &'static str)],
+}
+
+impl Font {
+    fn lookup(&self, cells: &[bool]) -> Option<char> {
+        self.glyphs.iter().find_map(|&(c, pattern)| {
+            let lit = pattern.bytes().filter(|&b| b != b'\n').map(|b| b == b'#');
+            lit.eq(cells.iter().copied()).then_some(c)
+        })
+    }
+}
+
+// The 4x6 letters from 2016 and 2022 onwards, one blank column between each.
+pub const SMALL: Font = Font {
+    width: 4,
+    height: 6,
+    pitch: 5,
+    glyphs: &[
+        ('A', ".##.\n#..#\n#..#\n####\n#..#\n#..#"),
+        ('B', "###.\n#..#\n###.\n#..#\n#..#\n###."),
+        ('C', ".##.\n#..#\n#...\n#...\n#..#\n.##."),
+        ('E', "####\n#...\n###.\n#...\n#...\n####"),
+        ('F', "####\n#...\n###.\n#...\n#...\n#..."),
+        ('G', ".##.\n#..#\n#...\n#.##\n#..#\n.###"),
+        ('H', "#..#\n#..#\n####\n#..#\n#..#\n#..#"),
+        ('I', ".###\n..#.\n..#.\n..#.\n..#.\n.###"),
+        ('J', "..##\n...#\n...#\n...#\n#..#\n.##."),
+        ('K', "#..#\n#.#.\n##..\n#.#.\n#.#.\n#..#"),
+        ('L', "#...\n#...\n#...\n#...\n#...\n####"),
+        ('O', ".##.\n#..#\n#..#\n#..#\n#..#\n.##."),
+        ('P', "###.\n#..#\n#..#\n###.\n#...\n#..."),
+        ('R', "###.\n#..#\n#..#\n###.\n#.#.\n#..#"),
+        ('S', ".###\n#...\n#...\n.##.\n...#\n###."),
+        ('U', "#..#\n#..#\n#..#\n#..#\n#..#\n.##."),
+        ('Z', "####\n...#\n..#.\n.#..\n#...\n####"),
+    ],
+};
+
+// The 6x10 letters from 2018, two blank columns between each.
+pub const LARGE: Font = Font {
+    width: 6,
+    height: 10,
+    pitch: 8,
+    glyphs: &[
+        (
+            'A',
+            "..##..\n.#..#.\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#",
+        ),
+        (
+            'B',
+            "#####.\n#....#\n#....#\n#....#\n#####.\n#....#\n#....#\n#....#\n#....#\n#####.",
+        ),
+        (
+            'C',
+            ".####.\n#....#\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#....#\n.####.",
+        ),
+        (
+            'E',
+            "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n######",
+        ),
+        (
+            'F',
+            "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n#.....",
+        ),
+        (
+            'G',
+            ".####.\n#....#\n#.....\n#.....\n#.....\n#..###\n#....#\n#....#\n#...##\n.###.#",
+        ),
+        (
+            'H',
+            "#....#\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#\n#....#",
+        ),
+        (
+            'J',
+            "...###\n....#.\n....#.\n....#.\n....#.\n....#.\n....#.\n#...#.\n#...#.\n.###..",
+        ),
+        (
+            'K',
+            "#....#\n#...#.\n#..#..\n#.#...\n##....\n##....\n#.#...\n#..#..\n#...#.\n#....#",
+        ),
+        (
+            'L',
+            "#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n######",
+        ),
+        (
+            'N',
+            "#....#\n##...#\n##...#\n#.#..#\n#.#..#\n#..#.#\n#..#.#\n#...##\n#...##\n#....#",
+        ),
+        (
+            'P',
+            "#####.\n#....#\n#....#\n#....#\n#####.\n#.....\n#.....\n#.....\n#.....\n#.....",
+        ),
+        (
+            'R',
+            "#####.\n#....#\n#....#\n#....#\n#####.\n#..#..\n#...#.\n#...#.\n#....#\n#....#",
+        ),
+        (
+            'X',
+            "#....#\n#....#\n.#..#.\n.#..#.\n..##..\n..##..\n.#..#.\n.#..#.\n#....#\n#....#",
+        ),
+        (
+            'Z',
+            "######\n.....#\n.....#\n....#.\n...#..\n..#...\n.#....\n#.....\n#.....\n######",
+        ),
+    ],
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownGlyph {
+    pub index: usize,
+    pub x: usize,
+    pub pattern: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OcrError {
+    UnsupportedHeight(usize),
+    // `text` has a '?' in place of each unknown glyph.
+    Unrecognized {
+        text: String,
+        glyphs: Vec<UnknownGlyph>,
+    },
+}
+
+impl Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedHeight(h) => write!(f, "no font is {h} pixels tall"),
+            Self::Unrecognized { text, glyphs } => {
+                writeln!(f, "unrecognized glyphs in {text:?}:")?;
+                for g in glyphs {
+                    writeln!(f, "glyph {} at column {}:\n{}", g.index, g.x, g.pattern)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for OcrError {}
+
+// Picks the font by the height of the grid. A blank screen reads as nothing, whatever its size.
+pub fn recognize<R: AsRef<[bool]>>(rows: &[R]) -> Result<String, OcrError> {
+    if rows.iter().all(|r| !r.as_ref().contains(&true)) {
+        return Ok(String::new());
+    }
+    match rows.len() {
+        6 => recognize_with(&SMALL, rows),
+        10 => recognize_with(&LARGE, rows),
+        h => Err(OcrError::UnsupportedHeight(h)),
+    }
+}
+
+pub fn recognize_with<R: AsRef<[bool]>>(font: &Font, rows: &[R]) -> Result<String, OcrError> {
+    if rows.len() != font.height {
+        return Err(OcrError::UnsupportedHeight(rows.len()));
+    }
+
+    let pixel = |x: usize, y: usize| rows[y].as_ref().get(x).copied().unwrap_or(false);
+    let width = rows.iter().map(|r| r.as_ref().len()).max().unwrap_or(0);
+
+    let mut text = String::new();
+    let mut unknown = Vec::new();
+
+    for (index, x0) in (0..width).step_by(font.pitch).enumerate() {
+        let mut cells = Vec::with_capacity(font.width * font.height);
+        for y in 0..font.height {
+            for x in x0..x0 + font.width {
+                cells.push(pixel(x, y));
+            }
+        }
+
+        if !cells.contains(&true) {
+            text.push(' ');
+        } else if let Some(c) = font.lookup(&cells) {
+            text.push(c);
+        } else {
+            text.push('?');
+            let pattern = cells
+                .chunks(font.width)
+                .map(|row| row.iter().map(|&b| if b { '#' } else { '.' }).collect())
+                .collect::<Vec<String>>()
+                .join("\n");
+            unknown.push(UnknownGlyph {
+                index,
+                x: x0,
+                pattern,
+            });
+        }
+    }
+
+    let text = text.trim().to_owned();
+
+    if unknown.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError::Unrecognized {
+            text,
+            glyphs: unknown,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Lays glyphs out left to right at the font's pitch.
+    fn screen(font: &Font, text: &str) -> Vec<Vec<bool>> {
+        let mut rows = vec![vec![false; font.pitch * text.len()]; font.height];
+        for (i, c) in text.chars().enumerate() {
+            let (_, pattern) = font.glyphs.iter().find(|(g, _)| *g == c).unwrap();
+            for (y, line) in pattern.lines().enumerate() {
+                for (x, b) in line.bytes().enumerate() {
+                    rows[y][i * font.pitch + x] = b == b'#';
+                }
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn reads_small_letters() {
+        let alphabet: String = SMALL.glyphs.iter().map(|(c, _)| c).collect();
+        assert_eq!(recognize(&screen(&SMALL, &alphabet)), Ok(alphabet));
+    }
+
+    #[test]
+    fn reads_large_letters() {
+        let alphabet: String = LARGE.glyphs.iter().map(|(c, _)| c).collect();
+        assert_eq!(recognize(&screen(&LARGE, &alphabet)), Ok(alphabet));
+    }
+
+    #[test]
+    fn gaps_read_as_spaces() {
+        let mut rows = screen(&SMALL, "AB");
+        let c = screen(&SMALL, "C");
+        for (row, c) in rows.iter_mut().zip(c) {
+            row.extend(vec![false; SMALL.pitch]);
+            row.extend(c);
+        }
+        assert_eq!(recognize(&rows).unwrap(), "AB C");
+    }
+
+    #[test]
+    fn reports_unknown_glyphs() {
+        let mut rows = screen(&SMALL, "HIH");
+        for row in &mut rows {
+            row[SMALL.pitch..SMALL.pitch + SMALL.width].fill(true);
+        }
+        let Err(OcrError::Unrecognized { text, glyphs }) = recognize(&rows) else {
+            panic!("read a solid block as a letter");
+        };
+        assert_eq!(text, "H?H");
+        assert_eq!(glyphs.len(), 1);
+        assert_eq!((glyphs[0].index, glyphs[0].x), (1, 5));
+        assert_eq!(glyphs[0].pattern, ["####"; 6].join("\n"));
+    }
+
+    #[test]
+    fn blank_and_odd_screens() {
+        assert_eq!(recognize::<Vec<bool>>(&[]), Ok(String::new()));
+        assert_eq!(recognize(&vec![vec![false; 40]; 6]), Ok(String::new()));
+        let rows = vec![vec![true; 4]; 7];
+        assert_eq!(recognize(&rows), Err(OcrError::UnsupportedHeight(7)));
+    }
+}
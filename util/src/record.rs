@@ -0,0 +1,395 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::part_path;
+
+static OUTPUT: OnceLock<PathBuf> = OnceLock::new();
+
+// Set by the runner when `--record <path>` is passed. The extension picks the format.
+pub fn enable(path: impl Into<PathBuf>) {
+    let _ = OUTPUT.set(path.into());
+}
+
+pub fn is_enabled() -> bool {
+    OUTPUT.get().is_some()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Gif,
+    Png,
+    Ppm,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gif" => Some(Self::Gif),
+            "png" => Some(Self::Png),
+            "ppm" => Some(Self::Ppm),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette(pub Vec<[u8; 3]>);
+
+impl Palette {
+    // Index 0 is the background colour, used for cells a frame doesn't cover.
+    pub fn new(colors: &[[u8; 3]]) -> Self {
+        assert!(!colors.is_empty() && colors.len() <= 256);
+        Self(colors.to_vec())
+    }
+
+    fn rgb(&self, index: u8) -> [u8; 3] {
+        self.0.get(index as usize).copied().unwrap_or([255, 0, 255])
+    }
+
+    fn flat(&self, len: usize) -> Vec<u8> {
+        (0..len).flat_map(|i| self.rgb(i as u8)).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    x0: i64,
+    y0: i64,
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+}
+
+impl Frame {
+    // Bounds are inclusive and in whatever coordinates the caller likes;
+    // frames are aligned against each other by those coordinates on export.
+    pub fn new(x0: i64, y0: i64, x1: i64, y1: i64) -> Self {
+        let width = (x1 - x0 + 1).max(0) as usize;
+        let height = (y1 - y0 + 1).max(0) as usize;
+        Self {
+            x0,
+            y0,
+            width,
+            height,
+            cells: vec![0; width * height],
+        }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = (i64, i64)>, color: u8) -> Self {
+        let points = Vec::from_iter(points);
+        let x0 = points.iter().map(|p| p.0).min().unwrap_or(0);
+        let y0 = points.iter().map(|p| p.1).min().unwrap_or(0);
+        let x1 = points.iter().map(|p| p.0).max().unwrap_or(-1);
+        let y1 = points.iter().map(|p| p.1).max().unwrap_or(-1);
+        let mut frame = Self::new(x0, y0, x1, y1);
+        for (x, y) in points {
+            frame.set(x, y, color);
+        }
+        frame
+    }
+
+    fn index(&self, x: i64, y: i64) -> Option<usize> {
+        let (dx, dy) = (x - self.x0, y - self.y0);
+        if dx < 0 || dy < 0 || dx as usize >= self.width || dy as usize >= self.height {
+            return None;
+        }
+        Some(dy as usize * self.width + dx as usize)
+    }
+
+    // Out-of-bounds writes are ignored, so callers can draw without clipping.
+    pub fn set(&mut self, x: i64, y: i64, color: u8) {
+        if let Some(i) = self.index(x, y) {
+            self.cells[i] = color;
+        }
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> u8 {
+        self.index(x, y).map_or(0, |i| self.cells[i])
+    }
+
+    fn bounds(&self) -> Option<(i64, i64, i64, i64)> {
+        if self.cells.is_empty() {
+            return None;
+        }
+        let x1 = self.x0 + self.width as i64 - 1;
+        let y1 = self.y0 + self.height as i64 - 1;
+        Some((self.x0, self.y0, x1, y1))
+    }
+}
+
+struct Canvas {
+    x0: i64,
+    y0: i64,
+    width: usize,
+    height: usize,
+    scale: usize,
+}
+
+impl Canvas {
+    fn fitting(frames: &[Frame], scale: usize) -> Self {
+        let bounds = frames.iter().filter_map(Frame::bounds);
+        let (x0, y0, x1, y1) = bounds
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+            .unwrap_or((0, 0, 0, 0));
+        Self {
+            x0,
+            y0,
+            width: (x1 - x0 + 1) as usize * scale,
+            height: (y1 - y0 + 1) as usize * scale,
+            scale,
+        }
+    }
+
+    fn pixels(&self, frame: &Frame) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.width * self.height);
+        for py in 0..self.height {
+            let y = self.y0 + (py / self.scale) as i64;
+            for px in 0..self.width {
+                let x = self.x0 + (px / self.scale) as i64;
+                pixels.push(frame.get(x, y));
+            }
+        }
+        pixels
+    }
+}
+
+struct Inner {
+    path: PathBuf,
+    format: Format,
+    palette: Palette,
+    frames: Vec<Frame>,
+    offered: usize,
+    every: usize,
+    scale: usize,
+    delay: u16,
+}
+
+// Collects frames from a solver and writes them out on `finish`.
+// When recording isn't enabled, this is a `None` and the frame closures are never called.
+pub struct Recorder {
+    inner: Option<Inner>,
+}
+
+impl Recorder {
+    pub fn new(palette: Palette) -> Self {
+        match OUTPUT.get() {
+            Some(path) => Self::to(path, palette),
+            None => Self::disabled(),
+        }
+    }
+
+    // Records to `path` whether or not `--record` was passed.
+    pub fn to(path: &Path, palette: Palette) -> Self {
+        let format = Format::from_path(path).unwrap_or(Format::Gif);
+        let inner = Inner {
+            path: part_path(path),
+            format,
+            palette,
+            frames: Vec::new(),
+            offered: 0,
+            every: 1,
+            scale: 4,
+            delay: 5,
+        };
+        Self { inner: Some(inner) }
+    }
+
+    pub fn disabled() -> Self {
+        Self { inner: None }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    // Only keep every `n`th frame offered.
+    pub fn every(mut self, n: usize) -> Self {
+        if let Some(inner) = &mut self.inner {
+            inner.every = n.max(1);
+        }
+        self
+    }
+
+    // Pixels per cell.
+    pub fn scale(mut self, scale: usize) -> Self {
+        if let Some(inner) = &mut self.inner {
+            inner.scale = scale.max(1);
+        }
+        self
+    }
+
+    // Hundredths of a second per frame (GIF only).
+    pub fn delay(mut self, delay: u16) -> Self {
+        if let Some(inner) = &mut self.inner {
+            inner.delay = delay;
+        }
+        self
+    }
+
+    pub fn frame(&mut self, f: impl FnOnce() -> Frame) {
+        if let Some(inner) = &mut self.inner {
+            if inner.offered % inner.every == 0 {
+                inner.frames.push(f());
+            }
+            inner.offered += 1;
+        }
+    }
+
+    // Like `frame`, but ignores `every`; for first and last states.
+    pub fn keyframe(&mut self, f: impl FnOnce() -> Frame) {
+        if let Some(inner) = &mut self.inner {
+            inner.frames.push(f());
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        let Some(inner) = self.inner else {
+            return Ok(());
+        };
+
+        if inner.frames.is_empty() {
+            return Ok(());
+        }
+
+        let canvas = Canvas::fitting(&inner.frames, inner.scale);
+        match inner.format {
+            Format::Gif => write_gif(&inner, &canvas),
+            Format::Png => write_sequence(&inner, &canvas, write_png),
+            Format::Ppm => write_sequence(&inner, &canvas, write_ppm),
+        }
+    }
+}
+
+fn numbered_path(path: &Path, i: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}-{i:05}.{ext}"))
+}
+
+fn write_gif(inner: &Inner, canvas: &Canvas) -> io::Result<()> {
+    let width = u16::try_from(canvas.width).map_err(io::Error::other)?;
+    let height = u16::try_from(canvas.height).map_err(io::Error::other)?;
+
+    let file = BufWriter::new(File::create(&inner.path)?);
+    let palette = inner.palette.flat(256);
+    let mut encoder = gif::Encoder::new(file, width, height, &palette).map_err(io::Error::other)?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(io::Error::other)?;
+
+    for frame in &inner.frames {
+        let pixels = canvas.pixels(frame);
+        let mut frame = gif::Frame::from_indexed_pixels(width, height, &pixels, None);
+        frame.delay = inner.delay;
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+    }
+
+    Ok(())
+}
+
+fn write_sequence(
+    inner: &Inner,
+    canvas: &Canvas,
+    write: fn(&Path, &Palette, &Canvas, &[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+    for (i, frame) in inner.frames.iter().enumerate() {
+        let pixels = canvas.pixels(frame);
+        write(
+            &numbered_path(&inner.path, i),
+            &inner.palette,
+            canvas,
+            &pixels,
+        )?;
+    }
+    Ok(())
+}
+
+fn write_png(path: &Path, palette: &Palette, canvas: &Canvas, pixels: &[u8]) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, canvas.width as u32, canvas.height as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette.flat(palette.0.len()));
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(pixels).map_err(io::Error::other)
+}
+
+fn write_ppm(path: &Path, palette: &Palette, canvas: &Canvas, pixels: &[u8]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "P6\n{} {}\n255", canvas.width, canvas.height)?;
+    for &p in pixels {
+        file.write_all(&palette.rgb(p))?;
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("util-record-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    fn palette() -> Palette {
+        Palette::new(&[[0, 0, 0], [255, 0, 0], [0, 255, 0]])
+    }
+
+    #[test]
+    fn frames_align_by_coordinates() {
+        let a = Frame::from_points([(0, 0)], 1);
+        let b = Frame::from_points([(2, 1)], 2);
+        let canvas = Canvas::fitting(&[a.clone(), b.clone()], 1);
+        assert_eq!((canvas.width, canvas.height), (3, 2));
+        assert_eq!(canvas.pixels(&a), [1, 0, 0, 0, 0, 0]);
+        assert_eq!(canvas.pixels(&b), [0, 0, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn writes_ppm_sequence() {
+        let path = scratch("seq.ppm");
+        let mut rec = Recorder::to(&path, palette()).scale(1).every(2);
+        for color in [1, 2, 1] {
+            rec.frame(|| Frame::from_points([(0, 0), (1, 0)], color));
+        }
+        rec.finish().unwrap();
+
+        let header = b"P6\n2 1\n255\n";
+        let first = std::fs::read(numbered_path(&path, 0)).unwrap();
+        assert_eq!(first[..header.len()], header[..]);
+        assert_eq!(first[header.len()..], [255, 0, 0, 255, 0, 0]);
+        // The second frame offered is skipped, so the third is next.
+        let second = std::fs::read(numbered_path(&path, 1)).unwrap();
+        assert_eq!(second[header.len()..], [255, 0, 0, 255, 0, 0]);
+        assert!(!numbered_path(&path, 2).exists());
+    }
+
+    #[test]
+    fn writes_gif_and_png() {
+        let gif = scratch("out.gif");
+        let mut rec = Recorder::to(&gif, palette());
+        rec.frame(|| Frame::from_points([(0, 0)], 1));
+        rec.keyframe(|| Frame::from_points([(3, 3)], 2));
+        rec.finish().unwrap();
+        assert!(std::fs::read(&gif).unwrap().starts_with(b"GIF89a"));
+
+        let png = scratch("out.png");
+        let mut rec = Recorder::to(&png, palette());
+        rec.frame(|| Frame::from_points([(0, 0)], 1));
+        rec.finish().unwrap();
+        let bytes = std::fs::read(numbered_path(&png, 0)).unwrap();
+        assert!(bytes.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn disabled_never_draws() {
+        let mut rec = Recorder::disabled();
+        rec.frame(|| panic!("drew a frame nobody wanted"));
+        assert!(!rec.is_enabled());
+        rec.finish().unwrap();
+    }
+}
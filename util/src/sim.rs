@@ -0,0 +1,364 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STEPPING: AtomicBool = AtomicBool::new(false);
+
+// Set by the runner when `--step` is passed.
+pub fn enable() {
+    STEPPING.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    STEPPING.load(Ordering::Relaxed)
+}
+
+pub trait Simulation {
+    fn step(&mut self);
+    fn is_done(&self) -> bool;
+    fn render(&self) -> String;
+    fn fingerprint(&self) -> u64;
+}
+
+pub fn hash_of<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Runs `sim` to completion and returns the number of steps taken.
+// With `--step`, this hands control to an interactive `Stepper` on the terminal first.
+pub fn run<S: Simulation>(sim: &mut S) -> usize {
+    run_with(sim, Vec::new())
+}
+
+// Like `run`, with `predicates` available to `until <name>` in the interactive prompt.
+pub fn run_with<'p, S: Simulation>(sim: &mut S, predicates: Vec<Named<'p, S>>) -> usize {
+    let mut stepper = Stepper::with_predicates(sim, predicates);
+    if is_enabled() {
+        let stdin = io::stdin();
+        stepper.interactive(stdin.lock(), io::stderr()).unwrap();
+    }
+    stepper.finish();
+    stepper.steps
+}
+
+pub type Predicate<'p, S> = Box<dyn FnMut(&S) -> bool + 'p>;
+
+pub type Named<'p, S> = (&'static str, Predicate<'p, S>);
+
+pub struct Stepper<'s, 'p, S> {
+    sim: &'s mut S,
+    steps: usize,
+    predicates: Vec<(String, Predicate<'p, S>)>,
+}
+
+impl<'s, 'p, S: Simulation> Stepper<'s, 'p, S> {
+    pub fn new(sim: &'s mut S) -> Self {
+        Self {
+            sim,
+            steps: 0,
+            predicates: Vec::new(),
+        }
+    }
+
+    pub fn with_predicates(sim: &'s mut S, predicates: Vec<Named<'p, S>>) -> Self {
+        let mut stepper = Self::new(sim);
+        let named = predicates.into_iter().map(|(n, p)| (n.to_owned(), p));
+        stepper.predicates.extend(named);
+        stepper
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn sim(&self) -> &S {
+        self.sim
+    }
+
+    // Makes `until <name>` available in the interactive prompt.
+    pub fn predicate(&mut self, name: &str, pred: impl FnMut(&S) -> bool + 'p) {
+        self.predicates.push((name.to_owned(), Box::new(pred)));
+    }
+
+    pub fn step(&mut self) -> bool {
+        if self.sim.is_done() {
+            return false;
+        }
+        self.sim.step();
+        self.steps += 1;
+        true
+    }
+
+    pub fn finish(&mut self) {
+        while self.step() {}
+    }
+
+    pub fn run_to(&mut self, n: usize) {
+        while self.steps < n && self.step() {}
+    }
+
+    // Always takes at least one step, so it can be repeated to find successive matches.
+    pub fn run_until(&mut self, mut pred: impl FnMut(&S) -> bool) -> bool {
+        while self.step() {
+            if pred(self.sim) {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn run_until_repeat(&mut self) -> bool {
+        let mut seen = HashSet::new();
+        seen.insert(self.sim.fingerprint());
+        self.run_until(|sim| !seen.insert(sim.fingerprint()))
+    }
+
+    fn run_until_named(&mut self, name: &str) -> Option<bool> {
+        let i = self.predicates.iter().position(|(n, _)| n == name)?;
+        // checked in place so the predicates keep their order for `help`
+        while self.step() {
+            if (self.predicates[i].1)(self.sim) {
+                return Some(true);
+            }
+        }
+        Some(false)
+    }
+
+    fn status(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "{}", self.sim.render())?;
+        write!(out, "step {} [{:016x}]", self.steps, self.sim.fingerprint())?;
+        if self.sim.is_done() {
+            write!(out, " (done)")?;
+        }
+        writeln!(out)
+    }
+
+    fn help(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "  <enter> | s [n]   step once, or n times")?;
+        writeln!(out, "  g <n>             run to step n")?;
+        writeln!(out, "  u <name>          run until a predicate holds")?;
+        writeln!(out, "  r                 run until a state repeats")?;
+        writeln!(out, "  p                 print the current state")?;
+        writeln!(out, "  c                 continue to the end")?;
+        if !self.predicates.is_empty() {
+            let names = Vec::from_iter(self.predicates.iter().map(|(n, _)| n.as_str()));
+            writeln!(out, "predicates: {}", names.join(", "))?;
+        }
+        Ok(())
+    }
+
+    // Reads commands until `c`, end of input, or the simulation finishing.
+    pub fn interactive(&mut self, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+        self.status(&mut out)?;
+        write!(out, "> ")?;
+        out.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            let cmd = words.next().unwrap_or("s");
+            let arg = words.next();
+            let count = arg.and_then(|a| a.parse::<usize>().ok());
+
+            match cmd {
+                "s" => {
+                    for _ in 0..count.unwrap_or(1) {
+                        self.step();
+                    }
+                }
+                "g" => match count {
+                    Some(n) => self.run_to(n),
+                    None => writeln!(out, "g needs a step number")?,
+                },
+                "u" => match arg.map(|name| self.run_until_named(name)) {
+                    Some(Some(found)) if !found => writeln!(out, "never happened")?,
+                    Some(Some(_)) => (),
+                    _ => self.help(&mut out)?,
+                },
+                "r" => {
+                    if !self.run_until_repeat() {
+                        writeln!(out, "no repeats")?;
+                    }
+                }
+                "p" => (),
+                "c" => return Ok(()),
+                _ => {
+                    self.help(&mut out)?;
+                    write!(out, "> ")?;
+                    out.flush()?;
+                    continue;
+                }
+            }
+
+            self.status(&mut out)?;
+            if self.sim.is_done() {
+                return Ok(());
+            }
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Walks 0, 1, .. around a loop of `len`, for `limit` steps.
+    struct Clock {
+        len: u32,
+        at: u32,
+        ticks: usize,
+        limit: usize,
+    }
+
+    impl Clock {
+        fn new(len: u32, limit: usize) -> Self {
+            Self {
+                len,
+                at: 0,
+                ticks: 0,
+                limit,
+            }
+        }
+    }
+
+    impl Simulation for Clock {
+        fn step(&mut self) {
+            self.at = (self.at + 1) % self.len;
+            self.ticks += 1;
+        }
+
+        fn is_done(&self) -> bool {
+            self.ticks == self.limit
+        }
+
+        fn render(&self) -> String {
+            format!("at {}", self.at)
+        }
+
+        fn fingerprint(&self) -> u64 {
+            hash_of(&self.at)
+        }
+    }
+
+    #[test]
+    fn steps_to_the_end() {
+        let mut clock = Clock::new(5, 7);
+        let mut stepper = Stepper::new(&mut clock);
+        stepper.run_to(3);
+        assert_eq!((stepper.steps(), stepper.sim().at), (3, 3));
+        stepper.run_to(100);
+        assert_eq!(stepper.steps(), 7);
+        assert!(!stepper.step());
+        assert_eq!(run(&mut Clock::new(5, 7)), 7);
+    }
+
+    #[test]
+    fn run_until_finds_successive_matches() {
+        let mut clock = Clock::new(3, 10);
+        let mut stepper = Stepper::new(&mut clock);
+        assert!(stepper.run_until(|c| c.at == 0));
+        assert_eq!(stepper.steps(), 3);
+        assert!(stepper.run_until(|c| c.at == 0));
+        assert_eq!(stepper.steps(), 6);
+        assert!(!stepper.run_until(|c| c.at == 5));
+        assert_eq!(stepper.steps(), 10);
+    }
+
+    #[test]
+    fn detects_repeats() {
+        let mut clock = Clock::new(4, 100);
+        let mut stepper = Stepper::new(&mut clock);
+        assert!(stepper.run_until_repeat());
+        assert_eq!(stepper.steps(), 4);
+        assert!(stepper.run_until_repeat());
+        assert_eq!(stepper.steps(), 8);
+
+        let mut clock = Clock::new(100, 10);
+        assert!(!Stepper::new(&mut clock).run_until_repeat());
+    }
+
+    #[test]
+    fn interactive_commands() {
+        let mut clock = Clock::new(4, 20);
+        let mut stepper = Stepper::new(&mut clock);
+        stepper.predicate("two", |c| c.at == 2);
+        let input = "\ns 2\ng 5\nu two\nr\nx\nc\ns\n";
+        let mut out = Vec::new();
+        stepper.interactive(input.as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        // 1, 3, 5, then the next 2 at 6, and back to it at 10; `c` stops before the last line
+        assert_eq!(stepper.steps(), 10);
+        for s in [
+            "step 0 ", "step 1 ", "step 3 ", "step 5 ", "step 6 ", "step 10 ",
+        ] {
+            assert!(out.contains(s), "{s:?} missing from {out}");
+        }
+        assert!(out.contains("predicates: two"));
+    }
+
+    #[test]
+    fn predicates_passed_in_are_named() {
+        let mut clock = Clock::new(4, 20);
+        let mut wraps = 0;
+        let predicates: Vec<Named<Clock>> = vec![
+            ("three", Box::new(|c: &Clock| c.at == 3)),
+            (
+                "second-wrap",
+                Box::new(move |c: &Clock| {
+                    wraps += usize::from(c.at == 0);
+                    wraps == 2
+                }),
+            ),
+        ];
+        let mut stepper = Stepper::with_predicates(&mut clock, predicates);
+        let mut out = Vec::new();
+        stepper
+            .interactive("u three\nu second-wrap\nu nope\nc\n".as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(stepper.steps(), 8);
+        assert!(out.contains("at 3\nstep 3 ["));
+        assert!(out.contains("at 0\nstep 8 ["));
+        assert!(out.contains("predicates: three, second-wrap"));
+    }
+
+    #[test]
+    fn predicates_keep_their_order() {
+        let mut clock = Clock::new(4, 20);
+        let mut stepper = Stepper::new(&mut clock);
+        for (name, at) in [("one", 1), ("two", 2), ("three", 3)] {
+            stepper.predicate(name, move |c| c.at == at);
+        }
+        let mut out = Vec::new();
+        stepper
+            .interactive("u one\nu three\nu two\nu one\nx\nc\n".as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(stepper.steps(), 9);
+        assert!(out.contains("predicates: one, two, three"), "{out}");
+    }
+
+    #[test]
+    fn interactive_stops_when_done() {
+        let mut clock = Clock::new(4, 2);
+        let mut stepper = Stepper::new(&mut clock);
+        let mut out = Vec::new();
+        stepper
+            .interactive("s 5\ns\n".as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(stepper.steps(), 2);
+        assert!(out.starts_with("at 0\nstep 0 ["));
+        assert!(out.contains("at 2\nstep 2 ["));
+        assert!(out.ends_with("] (done)\n"));
+    }
+}
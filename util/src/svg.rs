@@ -0,0 +1,273 @@
+use std::fmt::{self, Display, Write as _};
+use std::io;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::part_path;
+
+static OUTPUT: OnceLock<PathBuf> = OnceLock::new();
+
+// Set by the runner when `--svg <path>` is passed.
+pub fn enable(path: impl Into<PathBuf>) {
+    let _ = OUTPUT.set(path.into());
+}
+
+pub fn is_enabled() -> bool {
+    OUTPUT.get().is_some()
+}
+
+// Stroke widths and font sizes are in output pixels, whatever the scale of the drawing.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Style {
+    fill: Option<String>,
+    stroke: Option<(String, f64)>,
+    opacity: Option<f64>,
+    font_size: Option<f64>,
+}
+
+impl Style {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fill(mut self, color: &str) -> Self {
+        self.fill = Some(color.to_owned());
+        self
+    }
+
+    pub fn stroke(mut self, color: &str, width: f64) -> Self {
+        self.stroke = Some((color.to_owned(), width));
+        self
+    }
+
+    pub fn opacity(mut self, opacity: f64) -> Self {
+        self.opacity = Some(opacity);
+        self
+    }
+
+    pub fn font_size(mut self, px: f64) -> Self {
+        self.font_size = Some(px);
+        self
+    }
+
+    fn write(&self, out: &mut String, scale: f64) -> fmt::Result {
+        let fill = self.fill.as_deref().unwrap_or("none");
+        write!(out, " fill=\"{}\"", escape(fill))?;
+        if let Some((color, width)) = &self.stroke {
+            write!(
+                out,
+                " stroke=\"{}\" stroke-width=\"{width}\"",
+                escape(color)
+            )?;
+            write!(out, " vector-effect=\"non-scaling-stroke\"")?;
+        }
+        if let Some(opacity) = self.opacity {
+            write!(out, " opacity=\"{opacity}\"")?;
+        }
+        if let Some(px) = self.font_size {
+            write!(out, " font-size=\"{}\"", px * scale)?;
+        }
+        Ok(())
+    }
+}
+
+type Point = (f64, f64);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Shape {
+    Polygon(Vec<Point>),
+    Polyline(Vec<Point>),
+    Rect(Point, Point),
+    Label(Point, String),
+}
+
+impl Shape {
+    fn points(&self) -> Vec<Point> {
+        match self {
+            Self::Polygon(pts) | Self::Polyline(pts) => pts.clone(),
+            Self::Rect((x, y), (w, h)) => vec![(*x, *y), (x + w, y + h)],
+            Self::Label(p, _) => vec![*p],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Svg {
+    shapes: Vec<(Shape, Style)>,
+    width: f64,
+    margin: f64,
+}
+
+impl Default for Svg {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Svg {
+    pub fn new() -> Self {
+        Self {
+            shapes: Vec::new(),
+            width: 1000.0,
+            margin: 0.02,
+        }
+    }
+
+    // Width of the output in pixels; the height follows from the drawing's aspect ratio.
+    pub fn width(mut self, px: f64) -> Self {
+        self.width = px;
+        self
+    }
+
+    pub fn polygon(&mut self, points: impl IntoIterator<Item = Point>, style: Style) {
+        let shape = Shape::Polygon(points.into_iter().collect());
+        self.shapes.push((shape, style));
+    }
+
+    pub fn polyline(&mut self, points: impl IntoIterator<Item = Point>, style: Style) {
+        let shape = Shape::Polyline(points.into_iter().collect());
+        self.shapes.push((shape, style));
+    }
+
+    pub fn rect(&mut self, x: f64, y: f64, w: f64, h: f64, style: Style) {
+        self.shapes.push((Shape::Rect((x, y), (w, h)), style));
+    }
+
+    pub fn label(&mut self, x: f64, y: f64, text: impl Display, style: Style) {
+        self.shapes
+            .push((Shape::Label((x, y), text.to_string()), style));
+    }
+
+    fn view_box(&self) -> (f64, f64, f64, f64) {
+        let pts = self.shapes.iter().flat_map(|(s, _)| s.points());
+        let (x0, y0, x1, y1) = pts.fold(
+            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |(x0, y0, x1, y1), (x, y)| (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        );
+        if x0 > x1 {
+            return (0.0, 0.0, 1.0, 1.0);
+        }
+        let (w, h) = ((x1 - x0).max(1.0), (y1 - y0).max(1.0));
+        let m = w.max(h) * self.margin;
+        (x0 - m, y0 - m, w + 2.0 * m, h + 2.0 * m)
+    }
+}
+
+fn write_points(out: &mut String, points: &[Point]) -> fmt::Result {
+    let pts = Vec::from_iter(points.iter().map(|(x, y)| format!("{x},{y}")));
+    write!(out, " points=\"{}\"", pts.join(" "))
+}
+
+// Safe both as text and inside a double-quoted attribute.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Display for Svg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (vx, vy, vw, vh) = self.view_box();
+        let scale = vw / self.width;
+        let height = vh / scale;
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{height:.0}\" viewBox=\"{vx} {vy} {vw} {vh}\">",
+            self.width
+        )?;
+
+        for (shape, style) in &self.shapes {
+            match shape {
+                Shape::Polygon(pts) => {
+                    out.push_str("<polygon");
+                    write_points(&mut out, pts)?;
+                }
+                Shape::Polyline(pts) => {
+                    out.push_str("<polyline");
+                    write_points(&mut out, pts)?;
+                }
+                Shape::Rect((x, y), (w, h)) => {
+                    write!(
+                        out,
+                        "<rect x=\"{x}\" y=\"{y}\" width=\"{w}\" height=\"{h}\""
+                    )?;
+                }
+                Shape::Label((x, y), _) => {
+                    write!(out, "<text x=\"{x}\" y=\"{y}\"")?;
+                }
+            }
+
+            let mut style = style.clone();
+            if let Shape::Label(..) = shape {
+                style.fill.get_or_insert_with(|| "black".to_owned());
+                style.font_size.get_or_insert(12.0);
+            }
+            style.write(&mut out, scale)?;
+
+            match shape {
+                Shape::Label(_, text) => writeln!(out, ">{}</text>", escape(text))?,
+                _ => writeln!(out, "/>")?,
+            }
+        }
+
+        writeln!(out, "</svg>")?;
+        f.write_str(&out)
+    }
+}
+
+// Writes the drawing to the `--svg` path, if there is one.
+pub fn export(svg: &Svg) -> io::Result<()> {
+    match OUTPUT.get() {
+        Some(path) => std::fs::write(part_path(path), svg.to_string()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_text_and_attributes() {
+        assert_eq!(
+            escape("a < b && c > \"d\""),
+            "a &lt; b &amp;&amp; c &gt; &quot;d&quot;"
+        );
+        // escaping once more escapes the ampersands it brought, as it should
+        assert_eq!(escape("&lt;"), "&amp;lt;");
+
+        let mut svg = Svg::new().width(100.0);
+        svg.label(0.0, 0.0, "<b>&", Style::new().fill("url(\"#g\")"));
+        let out = svg.to_string();
+        assert!(out.contains(">&lt;b&gt;&amp;</text>"), "{out}");
+        assert!(out.contains(" fill=\"url(&quot;#g&quot;)\""), "{out}");
+    }
+
+    #[test]
+    fn fits_the_view_box() {
+        let mut svg = Svg::new().width(200.0);
+        svg.rect(0.0, 0.0, 100.0, 50.0, Style::new().stroke("red", 1.0));
+        svg.polyline([(0.0, 0.0), (100.0, 50.0)], Style::new());
+        let out = svg.to_string();
+        let lines = Vec::from_iter(out.lines());
+        assert_eq!(
+            lines[0],
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"200\" height=\"104\" viewBox=\"-2 -2 104 54\">"
+        );
+        assert_eq!(
+            lines[1],
+            "<rect x=\"0\" y=\"0\" width=\"100\" height=\"50\" fill=\"none\" stroke=\"red\" stroke-width=\"1\" vector-effect=\"non-scaling-stroke\"/>"
+        );
+        assert_eq!(lines[2], "<polyline points=\"0,0 100,50\" fill=\"none\"/>");
+        assert_eq!(lines[3], "</svg>");
+    }
+
+    #[test]
+    fn empty_drawing() {
+        let out = Svg::new().to_string();
+        assert!(out.contains("viewBox=\"0 0 1 1\""), "{out}");
+    }
+}